@@ -0,0 +1,378 @@
+//! Archives read as directory trees, so that `DirCmp` can compare them.
+//!
+//! Supported formats, each behind its cargo feature:
+//!  - tar -- feature `tar`, optionally compressed with any format enabled
+//!    for `CmpOptions::decompress()` (`.tar.gz` needs `gzip` as well)
+//!  - zip -- feature `zip`
+//!
+//! All members are loaded into a `MemFs` when the archive is opened, so the
+//! whole uncompressed content is held in memory. They are addressed below the path of the archive itself, so member `src/lib.rs` of
+//! `release.tar.gz` is found at `release.tar.gz/src/lib.rs`.
+
+#[cfg(feature = "zip")]
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::stat;
+use crate::vfs::{relative_path, MemFs};
+
+/// A recognised archive format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    #[cfg(feature = "tar")]
+    Tar,
+    #[cfg(feature = "zip")]
+    Zip,
+}
+
+/// Recognise the archive format of a file from its first bytes.
+pub fn detect(path: impl AsRef<Path>) -> io::Result<Option<Kind>> {
+    let mut header = [0; 512];
+    #[cfg(feature = "zip")]
+    {
+        let len = crate::read_full(&mut File::open(path.as_ref())?, &mut header)?;
+        if header[..len].starts_with(b"PK\x03\x04") || header[..len].starts_with(b"PK\x05\x06") {
+            return Ok(Some(Kind::Zip));
+        }
+    }
+    #[cfg(feature = "tar")]
+    {
+        let len = crate::read_full(
+            &mut crate::compress::open(&crate::vfs::RealFs, path.as_ref())?,
+            &mut header,
+        )?;
+        if len == header.len() && &header[257..262] == b"ustar" {
+            return Ok(Some(Kind::Tar));
+        }
+    }
+    Ok(None)
+}
+
+/// Load all members of an archive into a tree rooted at the archive path.
+///
+/// Fails with `InvalidData` on malformed archives, and on tar hard links
+/// whose target is not a file listed before them.
+pub fn open(path: impl AsRef<Path>) -> io::Result<MemFs> {
+    let mut tree = MemFs::new(path.as_ref());
+    match detect(path.as_ref())? {
+        #[cfg(feature = "tar")]
+        Some(Kind::Tar) => load_tar(&mut tree)?,
+        #[cfg(feature = "zip")]
+        Some(Kind::Zip) => load_zip(&mut tree)?,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: not a recognised archive", path.as_ref().display()),
+            ))
+        }
+    }
+    Ok(tree)
+}
+
+#[cfg(feature = "tar")]
+fn load_tar(tree: &mut MemFs) -> io::Result<()> {
+    use crate::vfs::FileSystem;
+    use tar::EntryType;
+
+    let mut tar = tar::Archive::new(crate::compress::open(&crate::vfs::RealFs, tree.root())?);
+    for member in tar.entries()? {
+        let mut member = member?;
+        let name = match relative_path(&member.path()?) {
+            Some(name) => name,
+            None => continue,
+        };
+        let header = member.header();
+        let mode = header.mode()?;
+        let uid = header.uid().unwrap_or(0) as u32;
+        let gid = header.gid().unwrap_or(0) as u32;
+        let mtime = header.mtime().unwrap_or(0) as f64;
+        let entry = match header.entry_type() {
+            EntryType::Directory => tree.add_dir(name),
+            EntryType::Regular | EntryType::Continuous => {
+                let mut data = Vec::with_capacity(capacity(member.size()));
+                member.read_to_end(&mut data)?;
+                tree.add_file(name, data)
+            }
+            EntryType::Symlink => match member.link_name()? {
+                Some(target) => tree.add_symlink(name, target),
+                None => continue,
+            },
+            EntryType::Link => {
+                // the target must be a file listed earlier in the archive
+                let mut data = Vec::new();
+                let target = member
+                    .link_name()?
+                    .and_then(|target| relative_path(&target));
+                match target.map(|target| tree.open(&tree.root().join(target))) {
+                    Some(Ok(mut file)) => file.read_to_end(&mut data)?,
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "{}: hard link {} to a file not found before it",
+                                tree.root().display(),
+                                name.display()
+                            ),
+                        ))
+                    }
+                };
+                tree.add_file(name, data)
+            }
+            EntryType::Char => tree.add_special(name, stat::S_IFCHR),
+            EntryType::Block => tree.add_special(name, stat::S_IFBLK),
+            EntryType::Fifo => tree.add_special(name, stat::S_IFIFO),
+            _ => continue,
+        };
+        entry.mode(mode).owner(uid, gid).mtime(mtime);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "zip")]
+fn load_zip(tree: &mut MemFs) -> io::Result<()> {
+    let mut zip = zip::ZipArchive::new(File::open(tree.root())?)?;
+    for i in 0..zip.len() {
+        let mut member = zip.by_index(i)?;
+        let name = match member.enclosed_name().and_then(|name| relative_path(&name)) {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut data = Vec::with_capacity(capacity(member.size()));
+        let ifmt = member.unix_mode().map_or(0, stat::S_IFMT);
+        let entry = if member.is_dir() {
+            tree.add_dir(name)
+        } else if [stat::S_IFCHR, stat::S_IFBLK, stat::S_IFIFO, stat::S_IFSOCK].contains(&ifmt) {
+            tree.add_special(name, ifmt)
+        } else if member.is_symlink() {
+            member.read_to_end(&mut data)?;
+            tree.add_symlink(name, String::from_utf8_lossy(&data).into_owned())
+        } else {
+            member.read_to_end(&mut data)?;
+            tree.add_file(name, data)
+        };
+        if let Some(mode) = member.unix_mode() {
+            entry.mode(mode);
+        }
+        if let Some(t) = member.last_modified() {
+            let days = days_from_civil(t.year().into(), t.month().into(), t.day().into());
+            let secs =
+                i64::from(t.hour()) * 3600 + i64::from(t.minute()) * 60 + i64::from(t.second());
+            entry.mtime((days * 86400 + secs) as f64);
+        }
+    }
+    Ok(())
+}
+
+/// The buffer to reserve for a member of `size` bytes. Sizes come from
+/// the archive headers, which may lie, so at most 1 MiB is reserved
+/// before reading.
+fn capacity(size: u64) -> usize {
+    size.min(1 << 20) as usize
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+#[cfg(feature = "zip")]
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DirCmpBuilder;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_dircmp_tar() {
+        use crate::vfs::FileSystem;
+
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_dircmp_tar");
+        let tree = test_dir.join("tree");
+        fs::create_dir_all(tree.join("sub")).unwrap();
+        fs::write(tree.join("same.txt"), b"same").unwrap();
+        fs::write(tree.join("sub").join("diff.txt"), b"on disk").unwrap();
+        fs::write(tree.join("only_tree.txt"), b"tree").unwrap();
+
+        let tar_path = test_dir.join("release.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&tar_path).unwrap());
+        for (name, data) in &[
+            ("./same.txt", &b"same"[..]),
+            ("sub/diff.txt", b"in archive"),
+            ("only_tar.txt", b"tar"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        header.set_cksum();
+        builder
+            .append_link(&mut header, "link.txt", "sub/diff.txt")
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_size(0);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_link(&mut header, "hard.txt", "only_tar.txt")
+            .unwrap();
+        builder.into_inner().unwrap();
+
+        let archive = open(&tar_path).unwrap();
+        assert_eq!(
+            archive.read_dir(&tar_path).unwrap(),
+            vec![
+                PathBuf::from("hard.txt"),
+                "link.txt".into(),
+                "only_tar.txt".into(),
+                "same.txt".into(),
+                "sub".into()
+            ]
+        );
+        let mut data = Vec::new();
+        let mut link = archive.open(&tar_path.join("link.txt")).unwrap();
+        link.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"in archive");
+
+        let x = DirCmpBuilder::new(&tar_path, &tree)
+            .archives(true)
+            .shallow(false)
+            .build()
+            .unwrap();
+        assert_eq!(
+            x.left_only,
+            vec![
+                PathBuf::from("hard.txt"),
+                "link.txt".into(),
+                "only_tar.txt".into()
+            ]
+        );
+        assert_eq!(x.right_only, vec![PathBuf::from("only_tree.txt")]);
+        assert_eq!(x.same_files, vec![PathBuf::from("same.txt")]);
+        assert_eq!(
+            x.subdirs[Path::new("sub")].diff_files,
+            vec![PathBuf::from("diff.txt")]
+        );
+        assert_eq!(x.subdirs[Path::new("sub")].left, tar_path.join("sub"));
+
+        // hard links to files missing or listed later are rejected
+        let broken = test_dir.join("broken.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&broken).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_size(0);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_link(&mut header, "hard.txt", "later.txt")
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "later.txt", &b"later"[..])
+            .unwrap();
+        builder.into_inner().unwrap();
+        let err = open(&broken).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // a header claiming exabytes is not trusted for allocation
+        let mut header = tar::Header::new_gnu();
+        header.set_path("huge").unwrap();
+        header.set_size(1 << 62);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let huge = test_dir.join("huge.tar");
+        let mut data = header.as_bytes().to_vec();
+        data.resize(2048, 0);
+        fs::write(&huge, data).unwrap();
+        assert!(open(&huge).is_err());
+    }
+
+    /// Write `same.txt` and `sub/diff.txt` as a tree, and with another
+    /// `sub/diff.txt` as an archive by `write`, then compare both.
+    #[cfg(any(all(feature = "tar", feature = "gzip"), feature = "zip"))]
+    fn check_archive(test_dir: &Path, write: impl FnOnce(&Path, &[(&str, &[u8])])) {
+        let _ = fs::remove_dir_all(test_dir);
+        let tree = test_dir.join("tree");
+        fs::create_dir_all(tree.join("sub")).unwrap();
+        fs::write(tree.join("same.txt"), b"same").unwrap();
+        fs::write(tree.join("sub").join("diff.txt"), b"on disk").unwrap();
+        let archive = test_dir.join("archive");
+        write(
+            &archive,
+            &[("same.txt", b"same"), ("sub/diff.txt", b"in archive")],
+        );
+
+        let x = DirCmpBuilder::new(&archive, &tree)
+            .archives(true)
+            .shallow(false)
+            .build()
+            .unwrap();
+        assert!(x.left_only.is_empty() && x.right_only.is_empty());
+        assert_eq!(x.same_files, vec![PathBuf::from("same.txt")]);
+        assert_eq!(
+            x.subdirs[Path::new("sub")].diff_files,
+            vec![PathBuf::from("diff.txt")]
+        );
+    }
+
+    #[cfg(all(feature = "tar", feature = "gzip"))]
+    #[test]
+    fn test_dircmp_tar_gz() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_dircmp_tar_gz");
+        check_archive(&test_dir, |path, members| {
+            let gz = GzEncoder::new(fs::File::create(path).unwrap(), Compression::default());
+            let mut builder = tar::Builder::new(gz);
+            for (name, data) in members {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder.append_data(&mut header, name, *data).unwrap();
+            }
+            builder.into_inner().unwrap().finish().unwrap();
+        });
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_dircmp_zip() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_dircmp_zip");
+        check_archive(&test_dir, |path, members| {
+            let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+            let options = SimpleFileOptions::default();
+            zip.add_directory("sub/", options).unwrap();
+            for (name, data) in members {
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(data).unwrap();
+            }
+            zip.finish().unwrap();
+        });
+    }
+}
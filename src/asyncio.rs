@@ -0,0 +1,287 @@
+//! Asynchronous file and directory comparison on top of tokio.
+//!
+//! Functions like in the crate root, but async:
+//!  - cmp(f1, f2, shallow: bool) -> bool
+//!  - cmpfiles(a, b, common) -> ([], [], [])
+//!  - DirCmpBuilder::build_async()
+//!
+//! All file system access goes through `tokio::fs`, so the executor thread
+//! is never blocked on I/O. A comparison is cancelled by dropping its
+//! future: no more files are opened or read once it is dropped. Results are
+//! shared with the synchronous `cmp()` through the same cache.
+//!
+//! `build_async()` walks the trees itself when only the `shallow`, `ignore`
+//! and `hide` options are set. Builders with any other option, such as
+//! `vfs::FileSystem` sources, archives, metadata or name matching, run the
+//! synchronous `build()` on tokio's blocking thread pool instead; dropping
+//! the future then does not stop the walk.
+//!
+//! Only available with the `tokio` feature.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! # async fn run() -> std::io::Result<()> {
+//! use filecmp::{asyncio, DirCmpBuilder};
+//!
+//! let same = asyncio::cmp("foo.txt", "bar.txt", false).await?;
+//! let x = DirCmpBuilder::new("dir1", "dir2").build_async().await?;
+//! x.report();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use tokio::fs::{self, File};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    cache_get, cache_insert, cmp_signatures, os, sig, CmpOptions, DirCmp, DirCmpBuilder,
+    NameMatching, BUFSIZE, FOLLOW_SYMLINKS_DEFAULT,
+};
+
+/// Compare two files without blocking the executor.
+///
+/// See [`crate::cmp`] for the meaning of the arguments and the result.
+pub async fn cmp(f1: impl AsRef<Path>, f2: impl AsRef<Path>, shallow: bool) -> io::Result<bool> {
+    let s1 = sig(stat(f1.as_ref()).await?);
+    let s2 = sig(stat(f2.as_ref()).await?);
+
+    if let Some(outcome) = cmp_signatures(&s1, &s2, shallow) {
+        return Ok(outcome);
+    }
+    if s1.st_size != s2.st_size {
+        return Ok(false);
+    }
+
+    let options = CmpOptions::new().shallow(shallow);
+    let key = (f1.as_ref().into(), f2.as_ref().into(), s1, s2, options);
+    let outcome = if let Some(outcome) = cache_get(&key) {
+        outcome
+    } else {
+        let outcome = do_cmp(f1.as_ref(), f2.as_ref()).await?;
+        cache_insert(key, outcome);
+        outcome
+    };
+
+    Ok(outcome)
+}
+
+/// Compare common files in two directories without blocking the executor.
+///
+/// See [`crate::cmpfiles`] for the meaning of the arguments and the result.
+pub async fn cmpfiles<A, B, C, D>(
+    dir1: A,
+    dir2: B,
+    common: D,
+    shallow: bool,
+) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>)>
+where
+    A: AsRef<Path>,
+    B: AsRef<Path>,
+    C: AsRef<Path>,
+    D: AsRef<[C]>,
+{
+    let mut res = (Vec::new(), Vec::new(), Vec::new());
+    for x in common.as_ref() {
+        let ax = dir1.as_ref().join(x);
+        let bx = dir2.as_ref().join(x);
+        let x = x.as_ref().to_path_buf();
+        match cmp(&ax, &bx, shallow).await {
+            Ok(true) => res.0.push(x),
+            Ok(false) => res.1.push(x),
+            Err(_) => res.2.push(x),
+        }
+    }
+    Ok(res)
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+impl DirCmpBuilder {
+    /// Walk both directories and build the comparison without blocking the
+    /// executor.
+    ///
+    /// Only plain walks of the real file system with `shallow()` are done
+    /// with asynchronous I/O. Any other option, such as `decompress()`,
+    /// `reflinks()`, `digest_cache()`, `xattrs()`, `metadata()` or other
+    /// file systems, runs `build()` on the blocking thread pool instead,
+    /// so the result is the same. Sparse files are read in full rather
+    /// than skipping their holes.
+    pub async fn build_async(&self) -> io::Result<DirCmp> {
+        if !self.is_plain() {
+            let builder = self.clone();
+            // the task is never cancelled, so it only fails by panicking
+            return match tokio::task::spawn_blocking(move || builder.build()).await {
+                Ok(result) => result,
+                Err(err) => std::panic::resume_unwind(err.into_panic()),
+            };
+        }
+        self.build_at_async(self.left.clone(), self.right.clone())
+            .await
+    }
+
+    /// Whether only the options walked by `build_at_async()` are set. Of
+    /// the file comparison options, that is `shallow` alone.
+    fn is_plain(&self) -> bool {
+        self.options == CmpOptions::new().shallow(self.options.shallow)
+            && !self.archives
+            && self.left_fs.is_none()
+            && self.right_fs.is_none()
+            && self.renames.is_none()
+            && self.follow_symlinks == FOLLOW_SYMLINKS_DEFAULT
+            && !self.metadata
+            && !self.merkle
+            && self.name_matching == NameMatching::Exact
+    }
+
+    fn build_at_async(&self, a: PathBuf, b: PathBuf) -> BoxFuture<'_, io::Result<DirCmp>> {
+        Box::pin(async move {
+            let left_list = self.filter(list_dir(&a).await?);
+            let right_list = self.filter(list_dir(&b).await?);
+            let mut dd = DirCmp::from_lists(&a, &b, self.filter_lists(), left_list, right_list);
+
+            for x in dd.common.clone() {
+                let a_stat = stat(&a.join(&x)).await;
+                let b_stat = stat(&b.join(&x)).await;
+                dd.classify(&x, a_stat, b_stat);
+            }
+
+            let (same, diff, funny) =
+                cmpfiles(&a, &b, &dd.common_files, self.options.shallow).await?;
+            dd.same_files = same;
+            dd.diff_files = diff;
+            dd.funny_files.extend(funny);
+
+            for x in dd.common_dirs.clone() {
+                let sd = self.build_at_async(a.join(&x), b.join(&x)).await?;
+                dd.subdirs.insert(x, sd);
+            }
+
+            Ok(dd)
+        })
+    }
+}
+
+async fn stat(path: &Path) -> io::Result<os::StatResult> {
+    os::from_metadata(&fs::metadata(path).await?)
+}
+
+async fn list_dir(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut names = Vec::new();
+    let mut entries = fs::read_dir(path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        names.push(PathBuf::from(entry.file_name()));
+    }
+    Ok(names)
+}
+
+async fn do_cmp(f1: &Path, f2: &Path) -> io::Result<bool> {
+    let mut f1 = File::open(f1).await?;
+    let mut f2 = File::open(f2).await?;
+    let mut buf1 = vec![0; BUFSIZE];
+    let mut buf2 = vec![0; BUFSIZE];
+    loop {
+        let len1 = read_full(&mut f1, &mut buf1).await?;
+        let len2 = read_full(&mut f2, &mut buf2).await?;
+        if len1 != len2 {
+            return Ok(false);
+        }
+        let read_size = len1;
+        if read_size == 0 {
+            return Ok(true);
+        }
+        if buf1[..read_size] != buf2[..read_size] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Read until `buf` is full or the end of file is reached, since tokio may
+/// hand back shorter chunks than requested.
+async fn read_full(f: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match f.read(&mut buf[len..]).await? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemFs;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_async_dircmp() {
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_async_dircmp");
+        let a = test_dir.join("a");
+        let b = test_dir.join("b");
+        for d in &[a.join("sub"), b.join("sub")] {
+            std::fs::create_dir_all(d).unwrap();
+        }
+        std::fs::write(a.join("same.txt"), b"same").unwrap();
+        std::fs::write(b.join("same.txt"), b"same").unwrap();
+        std::fs::write(a.join("sub").join("diff.txt"), b"left").unwrap();
+        std::fs::write(b.join("sub").join("diff.txt"), b"right").unwrap();
+        std::fs::write(b.join("only_b.txt"), b"b").unwrap();
+
+        assert!(cmp(a.join("same.txt"), b.join("same.txt"), false)
+            .await
+            .unwrap());
+
+        let x = DirCmpBuilder::new(&a, &b)
+            .shallow(false)
+            .build_async()
+            .await
+            .unwrap();
+        assert_eq!(x.same_files, vec![PathBuf::from("same.txt")]);
+        assert_eq!(x.right_only, vec![PathBuf::from("only_b.txt")]);
+        assert_eq!(x.common_dirs, vec![PathBuf::from("sub")]);
+        let sub = &x.subdirs[Path::new("sub")];
+        assert_eq!(sub.diff_files, vec![PathBuf::from("diff.txt")]);
+
+        // other sources go through the synchronous build()
+        let mut right = MemFs::new("right");
+        right.add_file("same.txt", "same");
+        let x = DirCmpBuilder::new(&a, "right")
+            .right_fs(right)
+            .shallow(false)
+            .build_async()
+            .await
+            .unwrap();
+        assert_eq!(x.same_files, vec![PathBuf::from("same.txt")]);
+        assert_eq!(x.left_only, vec![PathBuf::from("sub")]);
+    }
+
+    #[test]
+    fn test_async_fallback() {
+        let plain = DirCmpBuilder::new("a", "b");
+        assert!(plain.clone().is_plain());
+        assert!(plain.clone().shallow(false).is_plain());
+        for builder in vec![
+            plain.clone().decompress(true),
+            plain.clone().reflinks(true),
+            plain.clone().digest_cache(true),
+            plain.clone().xattrs(["user.*"]),
+            plain.clone().metadata(true),
+            plain.clone().follow_symlinks(false),
+            plain.clone().merkle(true),
+            plain.clone().renames(true),
+            plain.clone().name_matching(NameMatching::CaseInsensitive),
+            plain.clone().right_fs(MemFs::new("b")),
+        ] {
+            assert!(!builder.is_plain(), "{:?}", builder);
+        }
+    }
+}
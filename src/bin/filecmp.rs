@@ -0,0 +1,403 @@
+//! Command-line front end, like demo() in filecmp.py.
+//!
+//! Compares two files or two directories. The exit status follows `cmp`
+//! and `diff -q`: 0 if they are the same, 1 if they differ, 2 on trouble.
+//!
+//! Subcommands:
+//!  - manifest DIR -- write a manifest of DIR, see `filecmp::manifest`,
+//!    or with `--mtree` an mtree(5) specification, see `filecmp::mtree`
+//!  - check FILE -- verify a `sha256sum` or `b2sum` file, see
+//!    `filecmp::checksum`
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+use clap::{value_t, App, AppSettings, Arg, ArgMatches, ErrorKind, SubCommand};
+use filecmp::checksum::{Algorithm, Check, Checksums};
+use filecmp::format::{self, Format};
+use filecmp::manifest::{ManifestBuilder, ManifestFs};
+use filecmp::mtree::{Spec, SpecBuilder};
+use filecmp::{cmp_with, CmpOptions, DirCmp, DirCmpBuilder, NameMatching};
+
+const SAME: i32 = 0;
+const DIFFERENT: i32 = 1;
+const TROUBLE: i32 = 2;
+
+fn main() {
+    let matches = App::new("filecmp")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author("owtotwo <owtotwo@163.com>")
+        .about("A filecmp tool like filecmp.py in python3 standard library.")
+        .arg(
+            Arg::with_name("recursive")
+                .short("r")
+                .long("recursive")
+                .help("Compare subdirectories recursively"),
+        )
+        .arg(ignore_arg())
+        .arg(hide_arg())
+        .arg(
+            Arg::with_name("deep")
+                .short("d")
+                .long("deep")
+                .help("Compare file contents instead of stat() signatures"),
+        )
+        .arg(
+            Arg::with_name("manifest")
+                .short("m")
+                .long("manifest")
+                .help("Compare directory A against manifest file B, with metadata"),
+        )
+        .arg(
+            Arg::with_name("mtree")
+                .long("mtree")
+                .conflicts_with("manifest")
+                .help("Verify directory A against mtree(5) specification B"),
+        )
+        .arg(
+            Arg::with_name("digest_cache")
+                .long("digest-cache")
+                .help("Compare contents by SHA-256 digests cached in user.filecmp.sha256 xattrs"),
+        )
+        .arg(
+            Arg::with_name("merkle")
+                .long("merkle")
+                .help("Skip subdirectories with equal Merkle digests of their contents"),
+        )
+        .arg(
+            Arg::with_name("names")
+                .long("names")
+                .value_name("MODE")
+                .possible_values(&NameMatching::NAMES)
+                .default_value("exact")
+                .help("Pair names differing by case, Unicode normalization or both"),
+        )
+        .arg(
+            Arg::with_name("no_dereference")
+                .short("P")
+                .long("no-dereference")
+                .help("Compare symbolic links by their targets instead of following them"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&Format::NAMES)
+                .default_value("text")
+                .help("Output format of directory comparisons"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .help("Color the tree format; auto colors terminals unless NO_COLOR is set"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("Print nothing, only set the exit status"),
+        )
+        .arg(
+            Arg::with_name("a")
+                .value_name("A")
+                .help("One file or folder you want to compare")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("b")
+                .value_name("B")
+                .help("Another file or folder you want to compare")
+                .required(true)
+                .index(2),
+        )
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(
+            SubCommand::with_name("manifest")
+                .about("Write a manifest of a directory's metadata and digests")
+                .arg(ignore_arg())
+                .arg(hide_arg())
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Write the manifest to FILE instead of stdout"),
+                )
+                .arg(
+                    Arg::with_name("mtree")
+                        .long("mtree")
+                        .help("Write an mtree(5) specification instead"),
+                )
+                .arg(
+                    Arg::with_name("dir")
+                        .value_name("DIR")
+                        .help("The directory to snapshot")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Verify the files listed in a sha256sum or b2sum checksum file")
+                .arg(
+                    Arg::with_name("algorithm")
+                        .short("a")
+                        .long("algorithm")
+                        .value_name("ALGORITHM")
+                        .default_value("sha256")
+                        .help("Algorithm of untagged lines: sha256, blake2b or blake2b-BITS"),
+                )
+                .arg(
+                    Arg::with_name("directory")
+                        .short("C")
+                        .long("directory")
+                        .value_name("DIR")
+                        .default_value(".")
+                        .help("Directory the listed paths are relative to"),
+                )
+                .arg(
+                    Arg::with_name("quiet")
+                        .short("q")
+                        .long("quiet")
+                        .help("Print nothing, only set the exit status"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("The checksum file, such as SHA256SUMS")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .get_matches_safe()
+        .unwrap_or_else(|err| match err.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => err.exit(),
+            _ => {
+                eprintln!("{}", err.message);
+                process::exit(TROUBLE);
+            }
+        });
+
+    let code = match matches.subcommand() {
+        ("manifest", Some(sub)) => manifest(sub),
+        ("check", Some(sub)) => check(sub),
+        _ => run(&matches),
+    };
+    process::exit(code);
+}
+
+fn ignore_arg() -> Arg<'static, 'static> {
+    Arg::with_name("ignore")
+        .short("x")
+        .long("ignore")
+        .value_name("NAME")
+        .multiple(true)
+        .number_of_values(1)
+        .help("Name to ignore, replacing the default list (repeatable)")
+}
+
+fn hide_arg() -> Arg<'static, 'static> {
+    Arg::with_name("hide")
+        .long("hide")
+        .value_name("NAME")
+        .multiple(true)
+        .number_of_values(1)
+        .help("Name to hide, replacing the default list (repeatable)")
+}
+
+/// The `manifest` subcommand.
+fn manifest(matches: &ArgMatches) -> i32 {
+    let dir = Path::new(matches.value_of_os("dir").unwrap());
+    let text = if matches.is_present("mtree") {
+        spec_text(dir, matches)
+    } else {
+        manifest_text(dir, matches)
+    };
+    let result = text.and_then(|text| match matches.value_of_os("output") {
+        Some(output) => fs::write(output, text),
+        None => io::stdout().write_all(text.as_bytes()),
+    });
+    match result {
+        Ok(()) => SAME,
+        Err(err) => {
+            eprintln!("filecmp: {}: {}", dir.display(), err);
+            TROUBLE
+        }
+    }
+}
+
+fn manifest_text(dir: &Path, matches: &ArgMatches) -> io::Result<String> {
+    let mut builder = ManifestBuilder::new(dir);
+    if let Some(names) = matches.values_of_os("ignore") {
+        builder = builder.ignore(names);
+    }
+    if let Some(names) = matches.values_of_os("hide") {
+        builder = builder.hide(names);
+    }
+    builder.build().map(|manifest| manifest.to_string())
+}
+
+fn spec_text(dir: &Path, matches: &ArgMatches) -> io::Result<String> {
+    let mut builder = SpecBuilder::new(dir);
+    if let Some(names) = matches.values_of_os("ignore") {
+        builder = builder.ignore(names);
+    }
+    if let Some(names) = matches.values_of_os("hide") {
+        builder = builder.hide(names);
+    }
+    builder.build().map(|spec| spec.to_string())
+}
+
+/// The `check` subcommand.
+fn check(matches: &ArgMatches) -> i32 {
+    let file = Path::new(matches.value_of_os("file").unwrap());
+    let dir = Path::new(matches.value_of_os("directory").unwrap());
+    let algorithm: Algorithm = match matches.value_of("algorithm").unwrap().parse() {
+        Ok(algorithm) => algorithm,
+        Err(err) => {
+            eprintln!("filecmp: {}", err);
+            return TROUBLE;
+        }
+    };
+    let sums = match Checksums::open(file, algorithm) {
+        Ok(sums) => sums,
+        Err(err) => {
+            eprintln!("filecmp: {}: {}", file.display(), err);
+            return TROUBLE;
+        }
+    };
+    let mut code = SAME;
+    for (path, check) in sums.verify(dir) {
+        if check != Check::Ok {
+            code = DIFFERENT;
+        }
+        if !matches.is_present("quiet") {
+            println!("{}: {}", path.display(), check);
+        }
+    }
+    code
+}
+
+fn run(matches: &ArgMatches) -> i32 {
+    let a = Path::new(matches.value_of_os("a").unwrap());
+    let b = Path::new(matches.value_of_os("b").unwrap());
+    let quiet = matches.is_present("quiet");
+    let shallow = !matches.is_present("deep");
+    let digest_cache = matches.is_present("digest_cache");
+
+    let manifest = matches.is_present("manifest");
+    let mtree = matches.is_present("mtree");
+
+    if !manifest && !mtree && (!a.is_dir() || !b.is_dir()) {
+        let options = CmpOptions::new()
+            .shallow(shallow)
+            .digest_cache(digest_cache);
+        let follow_symlinks = !matches.is_present("no_dereference");
+        return match cmp_files(a, b, &options, follow_symlinks) {
+            Ok(true) => SAME,
+            Ok(false) => {
+                if !quiet {
+                    println!("Files {} and {} differ", a.display(), b.display());
+                }
+                DIFFERENT
+            }
+            Err(err) => {
+                eprintln!("filecmp: {} and {}: {}", a.display(), b.display(), err);
+                TROUBLE
+            }
+        };
+    }
+
+    let dd = match build(a, b, matches) {
+        Ok(dd) => dd,
+        Err(err) => {
+            eprintln!("filecmp: {}", err);
+            return TROUBLE;
+        }
+    };
+    let recursive = matches.is_present("recursive");
+    if !quiet {
+        let format = value_t!(matches, "format", Format).unwrap_or_else(|e| e.exit());
+        let color = match matches.value_of("color") {
+            Some("always") => true,
+            Some("never") => false,
+            _ => format::use_color(),
+        };
+        print!("{}", dd.display(format, recursive).color(color));
+    }
+    status(&dd, recursive)
+}
+
+/// Compare files `a` and `b`. Unless `follow_symlinks`, symbolic links
+/// are the same only as links with the same target, like in directories.
+fn cmp_files(a: &Path, b: &Path, options: &CmpOptions, follow_symlinks: bool) -> io::Result<bool> {
+    if !follow_symlinks {
+        let a_link = fs::symlink_metadata(a)?.file_type().is_symlink();
+        let b_link = fs::symlink_metadata(b)?.file_type().is_symlink();
+        if a_link || b_link {
+            return Ok(a_link && b_link && fs::read_link(a)? == fs::read_link(b)?);
+        }
+    }
+    cmp_with(a, b, options)
+}
+
+/// Compare directory `a` against directory, manifest or mtree
+/// specification `b`.
+fn build(a: &Path, b: &Path, matches: &ArgMatches) -> io::Result<DirCmp> {
+    let mut builder = if matches.is_present("mtree") {
+        let spec: Spec = fs::read_to_string(b)
+            .and_then(|text| text.parse())
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", b.display(), err)))?;
+        spec.builder(a, b)
+    } else {
+        DirCmpBuilder::new(a, b)
+            .shallow(!matches.is_present("deep"))
+            .follow_symlinks(!matches.is_present("no_dereference"))
+    };
+    builder = builder
+        .digest_cache(matches.is_present("digest_cache"))
+        .merkle(matches.is_present("merkle"))
+        .name_matching(value_t!(matches, "names", NameMatching).unwrap_or_else(|e| e.exit()));
+    if matches.is_present("manifest") {
+        let parsed = fs::read_to_string(b)
+            .and_then(|text| text.parse())
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", b.display(), err)))?;
+        builder = builder.right_fs(ManifestFs::new(b, parsed)).metadata(true);
+    }
+    if let Some(names) = matches.values_of_os("ignore") {
+        builder = builder.ignore(names);
+    }
+    if let Some(names) = matches.values_of_os("hide") {
+        builder = builder.hide(names);
+    }
+    builder.build()
+}
+
+/// Exit status of a directory comparison.
+fn status(dd: &DirCmp, recursive: bool) -> i32 {
+    let mut code = if !dd.funny_files.is_empty() {
+        TROUBLE
+    } else if !dd.left_only.is_empty()
+        || !dd.right_only.is_empty()
+        || !dd.diff_files.is_empty()
+        || !dd.common_funny.is_empty()
+        || !dd.meta_diffs.is_empty()
+    {
+        DIFFERENT
+    } else {
+        SAME
+    };
+    if recursive {
+        for sd in dd.subdirs.values() {
+            code = code.max(status(sd, recursive));
+        }
+    }
+    code
+}
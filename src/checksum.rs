@@ -0,0 +1,315 @@
+//! Checksum files as written by `sha256sum` and `b2sum`.
+//!
+//! `Checksums::parse()` reads the formats of GNU coreutils:
+//!
+//! ```text
+//! <hex>  <path>               text mode
+//! <hex> *<path>               binary mode
+//! SHA256 (<path>) = <hex>     BSD style, from `--tag`
+//! BLAKE2b-256 (<path>) = <hex>
+//! ```
+//!
+//! Lines starting with `\` have `\\`, `\n` and `\r` escaped in the path.
+//! The algorithm of untagged lines is given by the caller; `b2sum -l`
+//! lengths are told by the length of the digest.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::env;
+//! use std::fs;
+//! use std::path::PathBuf;
+//! use filecmp::checksum::{Algorithm, Check, Checksums};
+//!
+//! let temp_dir = env::temp_dir().join("filecmp_checksum_example");
+//! fs::create_dir_all(&temp_dir).unwrap();
+//! fs::write(temp_dir.join("hello.txt"), b"hello").unwrap();
+//!
+//! let sums = Checksums::parse(
+//!     "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  hello.txt\n",
+//!     Algorithm::Sha256,
+//! )
+//! .unwrap();
+//! assert_eq!(
+//!     sums.verify(&temp_dir),
+//!     vec![(PathBuf::from("hello.txt"), Check::Ok)]
+//! );
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::digest;
+
+/// A digest algorithm of checksum files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// SHA-256, as `sha256sum`.
+    Sha256,
+    /// BLAKE2b with a digest of the given bytes, as `b2sum`.
+    Blake2b(usize),
+}
+
+impl Algorithm {
+    /// Return the digest of everything `reader` yields, in lowercase hex.
+    pub fn digest(&self, reader: impl Read) -> io::Result<String> {
+        match *self {
+            Algorithm::Sha256 => digest::sha256(reader),
+            Algorithm::Blake2b(len) => digest::blake2b(reader, len),
+        }
+    }
+
+    /// The digest length in bytes.
+    fn size(&self) -> usize {
+        match *self {
+            Algorithm::Sha256 => 32,
+            Algorithm::Blake2b(len) => len,
+        }
+    }
+
+    /// The tag of BSD style lines, such as `BLAKE2b-256`.
+    // is_multiple_of() needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "SHA256" => Some(Algorithm::Sha256),
+            "BLAKE2b" => Some(Algorithm::Blake2b(64)),
+            _ => {
+                let bits: usize = tag.strip_prefix("BLAKE2b-")?.parse().ok()?;
+                if bits % 8 == 0 && (8..=512).contains(&bits) {
+                    Some(Algorithm::Blake2b(bits / 8))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Accepts `sha256`, `blake2b` and `b2` (512 bits), or `blake2b-<bits>`.
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(Algorithm::Sha256),
+            "blake2b" | "b2" => Ok(Algorithm::Blake2b(64)),
+            lower => lower
+                .strip_prefix("blake2b-")
+                .and_then(|bits| Algorithm::from_tag(&format!("BLAKE2b-{}", bits)))
+                .ok_or_else(|| format!("unknown algorithm '{}'", s)),
+        }
+    }
+}
+
+/// The entries of a checksum file, in file order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Checksums {
+    pub entries: Vec<Checksum>,
+}
+
+/// One line of a checksum file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checksum {
+    pub path: PathBuf,
+    pub algorithm: Algorithm,
+    /// Lowercase hex digest.
+    pub digest: String,
+    /// Whether the line has the binary marker `*`; both modes read the
+    /// same bytes on POSIX systems.
+    pub binary: bool,
+}
+
+/// The result of checking one entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Ok,
+    Failed,
+    /// The file cannot be opened or read.
+    Missing,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Check::Ok => "OK",
+            Check::Failed => "FAILED",
+            Check::Missing => "missing",
+        })
+    }
+}
+
+impl Checksums {
+    /// Parse the text of a checksum file, with `algorithm` for untagged
+    /// lines. Blank lines and `#` comments are skipped; other malformed
+    /// lines fail with `InvalidData`.
+    pub fn parse(text: &str, algorithm: Algorithm) -> io::Result<Self> {
+        let mut entries = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_line(line, algorithm).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: improperly formatted checksum line", i + 1),
+                )
+            })?;
+            entries.push(entry);
+        }
+        Ok(Checksums { entries })
+    }
+
+    /// Read and parse the checksum file `path`.
+    pub fn open(path: impl AsRef<Path>, algorithm: Algorithm) -> io::Result<Self> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Checksums::parse(&text, algorithm)
+    }
+
+    /// Check every entry, with relative paths taken from `dir`.
+    pub fn verify(&self, dir: impl AsRef<Path>) -> Vec<(PathBuf, Check)> {
+        let dir = dir.as_ref();
+        self.entries
+            .iter()
+            .map(|entry| {
+                let check = match File::open(dir.join(&entry.path))
+                    .and_then(|file| entry.algorithm.digest(file))
+                {
+                    Ok(digest) if digest == entry.digest => Check::Ok,
+                    Ok(_) => Check::Failed,
+                    Err(_) => Check::Missing,
+                };
+                (entry.path.clone(), check)
+            })
+            .collect()
+    }
+}
+
+fn parse_line(line: &str, algorithm: Algorithm) -> Option<Checksum> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (algorithm, digest, binary, name) = match line.find(" (") {
+        // BSD style: the path may contain ") = ", the digest does not
+        Some(open) if Algorithm::from_tag(&line[..open]).is_some() => {
+            let close = line.rfind(") = ")?;
+            let algorithm = Algorithm::from_tag(&line[..open])?;
+            let name = line.get(open + 2..close)?;
+            (algorithm, &line[close + 4..], true, name)
+        }
+        _ => {
+            let (digest, rest) = line.split_once(' ')?;
+            let algorithm = match algorithm {
+                Algorithm::Blake2b(_) if (1..=128).contains(&digest.len()) => {
+                    Algorithm::Blake2b(digest.len() / 2)
+                }
+                _ => algorithm,
+            };
+            match rest.strip_prefix('*') {
+                Some(name) => (algorithm, digest, true, name),
+                None => (
+                    algorithm,
+                    digest,
+                    false,
+                    rest.strip_prefix(' ').unwrap_or(rest),
+                ),
+            }
+        }
+    };
+    if digest.len() != algorithm.size() * 2 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    if name.is_empty() {
+        return None;
+    }
+    let path = if escaped {
+        unescape(name)?
+    } else {
+        name.to_string()
+    };
+    Some(Checksum {
+        path: PathBuf::from(path),
+        algorithm,
+        digest: digest.to_ascii_lowercase(),
+        binary,
+    })
+}
+
+/// Undo the escaping of GNU coreutils.
+fn unescape(name: &str) -> Option<String> {
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => out.push('\\'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_checksums() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_checksums");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(test_dir.join("sub")).unwrap();
+        fs::write(test_dir.join("abc"), "abc").unwrap();
+        fs::write(test_dir.join("sub").join("a b"), "abc").unwrap();
+        fs::write(test_dir.join("new\nline"), "abc").unwrap();
+        fs::write(test_dir.join("bad"), "abd").unwrap();
+
+        let sha = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let b2 = "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319";
+        let text = format!(
+            "# release checksums\n\
+             {sha}  abc\n\
+             {sha} *sub/a b\n\
+             \\{sha}  new\\nline\n\
+             \n\
+             SHA256 (bad) = {sha}\n\
+             BLAKE2b-256 (gone) = {b2}\n",
+            sha = sha.to_ascii_uppercase(),
+            b2 = b2,
+        );
+        let sums = Checksums::parse(&text, Algorithm::Sha256).unwrap();
+        assert_eq!(sums.entries.len(), 5);
+        assert!(!sums.entries[0].binary && sums.entries[1].binary);
+        assert_eq!(sums.entries[0].digest, sha);
+        assert_eq!(sums.entries[4].algorithm, Algorithm::Blake2b(32));
+        assert_eq!(
+            sums.verify(&test_dir),
+            vec![
+                (PathBuf::from("abc"), Check::Ok),
+                (PathBuf::from("sub/a b"), Check::Ok),
+                (PathBuf::from("new\nline"), Check::Ok),
+                (PathBuf::from("bad"), Check::Failed),
+                (PathBuf::from("gone"), Check::Missing),
+            ]
+        );
+
+        // `b2sum -l 256` lines, told by their length
+        let line = format!("{}  abc\n", b2);
+        let sums = Checksums::parse(&line, Algorithm::Blake2b(64)).unwrap();
+        assert_eq!(sums.verify(&test_dir)[0].1, Check::Ok);
+        let err = Checksums::parse("ba7816bf  abc\n", Algorithm::Sha256).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,117 @@
+//! Transparent decompression of compressed files for comparison.
+//!
+//! Formats are recognised by their magic bytes, not by file extension.
+//! Each format needs its cargo feature:
+//!  - gzip -- feature `gzip`
+//!  - xz -- feature `xz`
+//!  - zstd -- feature `zstd`
+//!
+//! Files in a format whose feature is disabled are treated as plain files.
+
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::vfs::FileSystem;
+
+/// A recognised compression format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "xz")]
+    Xz,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Magic bytes of each enabled format.
+const MAGICS: &[(&[u8], Format)] = &[
+    #[cfg(feature = "gzip")]
+    (b"\x1f\x8b", Format::Gzip),
+    #[cfg(feature = "xz")]
+    (b"\xfd7zXZ\x00", Format::Xz),
+    #[cfg(feature = "zstd")]
+    (b"\x28\xb5\x2f\xfd", Format::Zstd),
+];
+
+/// Recognise the compression format of a file from its first bytes.
+pub fn detect(fs: &dyn FileSystem, path: &Path) -> io::Result<Option<Format>> {
+    let mut magic = [0; 6];
+    let len = crate::read_full(&mut fs.open(path)?, &mut magic)?;
+    Ok(detect_magic(&magic[..len]))
+}
+
+fn detect_magic(magic: &[u8]) -> Option<Format> {
+    MAGICS
+        .iter()
+        .find(|(prefix, _)| magic.starts_with(prefix))
+        .map(|&(_, format)| format)
+}
+
+/// Open a file for reading its decompressed content, or its raw content if
+/// it is not in a recognised format.
+pub fn open<'a>(fs: &'a dyn FileSystem, path: &Path) -> io::Result<Box<dyn Read + 'a>> {
+    let format = detect(fs, path)?;
+    let file = fs.open(path)?;
+    Ok(match format {
+        #[cfg(feature = "gzip")]
+        Some(Format::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        #[cfg(feature = "xz")]
+        Some(Format::Xz) => Box::new(xz2::read::XzDecoder::new_multi_decoder(file)),
+        #[cfg(feature = "zstd")]
+        Some(Format::Zstd) => Box::new(zstd::stream::read::Decoder::new(file)?),
+        None => file,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_magic() {
+        assert_eq!(detect_magic(b""), None);
+        assert_eq!(detect_magic(b"plain text"), None);
+        #[cfg(feature = "gzip")]
+        assert_eq!(detect_magic(b"\x1f\x8b\x08\x00"), Some(Format::Gzip));
+        #[cfg(feature = "xz")]
+        assert_eq!(detect_magic(b"\xfd7zXZ\x00"), Some(Format::Xz));
+        #[cfg(feature = "zstd")]
+        assert_eq!(detect_magic(b"\x28\xb5\x2f\xfd\x00"), Some(Format::Zstd));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_cmp_decompress() {
+        use crate::{cmp, cmp_with, CmpOptions};
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::env;
+        use std::fs;
+        use std::io::Write;
+
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_cmp_decompress");
+        fs::create_dir_all(&test_dir).unwrap();
+
+        let payload = b"the same payload, compressed twice".repeat(100);
+        let fast_path = test_dir.join("fast.gz");
+        let best_path = test_dir.join("best.bin");
+        let plain_path = test_dir.join("plain.txt");
+        for (path, level) in &[
+            (&fast_path, Compression::fast()),
+            (&best_path, Compression::best()),
+        ] {
+            let mut encoder = GzEncoder::new(fs::File::create(path).unwrap(), *level);
+            encoder.write_all(&payload).unwrap();
+            encoder.finish().unwrap();
+        }
+        fs::write(&plain_path, &payload).unwrap();
+
+        let options = CmpOptions::new().shallow(false).decompress(true);
+        assert!(!cmp(&fast_path, &best_path, false).unwrap());
+        assert!(cmp_with(&fast_path, &best_path, &options).unwrap());
+        assert!(cmp_with(&fast_path, &plain_path, &options).unwrap());
+    }
+}
@@ -0,0 +1,314 @@
+//! Lazy iteration over the differences between two trees.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{CmpOptions, DirCmpBuilder, Source};
+
+/// A difference found by `compare_trees()` or `DirCmpBuilder::differences()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// Path relative to the roots of both trees.
+    pub path: PathBuf,
+    pub kind: DifferenceKind,
+}
+
+/// What differs at a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DifferenceKind {
+    /// Only found in the left tree; directories are not walked.
+    LeftOnly,
+    /// Only found in the right tree; directories are not walked.
+    RightOnly,
+    /// A file whose content differs.
+    Different,
+    /// Of another type in each tree, neither file nor directory, or not
+    /// readable.
+    Funny,
+}
+
+/// Iterator over the differences between two trees, see
+/// `DirCmpBuilder::differences()`.
+pub struct Differences {
+    builder: DirCmpBuilder,
+    sources: Option<(Source, Source)>,
+    /// Work left to do, the next step last.
+    pending: Vec<Step>,
+}
+
+enum Step {
+    Found(Difference),
+    /// A file found in both trees, compared when reached, with its path
+    /// in the left tree and in the right one.
+    File(PathBuf, PathBuf),
+    /// A directory found in both trees, listed when reached.
+    Dir(PathBuf, PathBuf),
+}
+
+impl DirCmpBuilder {
+    /// Iterate over the differences between both directories.
+    ///
+    /// Nothing is read until the iterator is advanced, and files are only
+    /// compared when reached, so stopping at the first difference is cheap.
+    /// Differences come in the order of `visit()`: for each directory,
+    /// left only, right only, differing files and funny cases, then
+    /// subdirectories. Errors listing a directory are yielded in place of
+    /// its entries.
+    pub fn differences(&self) -> Differences {
+        Differences {
+            builder: self.clone(),
+            sources: None,
+            pending: vec![Step::Dir(PathBuf::new(), PathBuf::new())],
+        }
+    }
+
+    /// Whether both directories are recursively identical, stopping at the
+    /// first difference. Entries which are neither files nor directories
+    /// count as differences.
+    pub fn equal(&self) -> io::Result<bool> {
+        match self.differences().next() {
+            None => Ok(true),
+            Some(Ok(_)) => Ok(false),
+            Some(Err(err)) => Err(err),
+        }
+    }
+}
+
+impl Differences {
+    /// Compare a file or list a directory found in both trees.
+    fn step(&mut self, step: Step) -> io::Result<Option<Difference>> {
+        if self.sources.is_none() {
+            let b = &self.builder;
+            self.sources = Some((
+                Source::open(&b.left_fs, &b.left, b.archives)?,
+                Source::open(&b.right_fs, &b.right, b.archives)?,
+            ));
+        }
+        let (left_src, right_src) = self.sources.as_ref().expect("sources are open");
+        let (a, b) = (&self.builder.left, &self.builder.right);
+        let (rel, right_rel) = match step {
+            Step::Found(difference) => return Ok(Some(difference)),
+            Step::File(rel, right_rel) => {
+                let same =
+                    self.builder
+                        .cmp_entry(left_src, &a.join(&rel), right_src, &b.join(&right_rel));
+                let kind = match same {
+                    Ok(true) => return Ok(None),
+                    Ok(false) => DifferenceKind::Different,
+                    Err(_) => DifferenceKind::Funny,
+                };
+                return Ok(Some(Difference { path: rel, kind }));
+            }
+            Step::Dir(rel, right_rel) => (rel, right_rel),
+        };
+
+        let dd =
+            self.builder
+                .list_level(left_src, &a.join(&rel), right_src, &b.join(&right_rel))?;
+        let paths = |x: &PathBuf| (rel.join(x), right_rel.join(dd.right_name(x)));
+        let found = |names: &[PathBuf], kind| -> Vec<Step> {
+            names
+                .iter()
+                .map(|x| {
+                    Step::Found(Difference {
+                        path: rel.join(x),
+                        kind,
+                    })
+                })
+                .collect()
+        };
+        let mut steps = found(&dd.left_only, DifferenceKind::LeftOnly);
+        steps.extend(found(&dd.right_only, DifferenceKind::RightOnly));
+        steps.extend(dd.common_files.iter().map(|x| {
+            let (path, right_path) = paths(x);
+            Step::File(path, right_path)
+        }));
+        steps.extend(found(&dd.funny_files, DifferenceKind::Funny));
+        steps.extend(found(&dd.common_funny, DifferenceKind::Funny));
+        steps.extend(dd.common_dirs.iter().map(|x| {
+            let (path, right_path) = paths(x);
+            Step::Dir(path, right_path)
+        }));
+        self.pending.extend(steps.into_iter().rev());
+        Ok(None)
+    }
+}
+
+impl Iterator for Differences {
+    type Item = io::Result<Difference>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(step) = self.pending.pop() {
+            match self.step(step) {
+                Ok(Some(difference)) => return Some(Ok(difference)),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        None
+    }
+}
+
+/// Iterate lazily over the differences between directories `a` and `b`,
+/// with the default ignore and hide lists and shallow file comparison.
+///
+/// # Example
+///
+/// ```rust
+/// use std::env;
+/// use std::fs;
+/// use filecmp::{compare_trees, DifferenceKind};
+///
+/// let temp_dir = env::temp_dir().join("filecmp_compare_trees_example");
+/// fs::create_dir_all(temp_dir.join("a")).unwrap();
+/// fs::create_dir_all(temp_dir.join("b")).unwrap();
+/// fs::write(temp_dir.join("b").join("new.txt"), b"new").unwrap();
+///
+/// let mut differences = compare_trees(temp_dir.join("a"), temp_dir.join("b"))
+///     .filter_map(Result::ok)
+///     .filter(|d| d.kind == DifferenceKind::RightOnly);
+/// assert_eq!(differences.next().unwrap().path, std::path::Path::new("new.txt"));
+/// ```
+pub fn compare_trees(a: impl AsRef<Path>, b: impl AsRef<Path>) -> Differences {
+    DirCmpBuilder::new(a, b).differences()
+}
+
+/// Whether directories `a` and `b` are recursively identical: same names,
+/// same types and same file contents as decided by `options`.
+///
+/// Returns on the first difference, without building a `DirCmp`. Uses the
+/// default ignore and hide lists; see `DirCmpBuilder::equal()` for others.
+///
+/// # Example
+///
+/// ```rust
+/// use std::env;
+/// use std::fs;
+/// use filecmp::{dirs_equal, CmpOptions};
+///
+/// let temp_dir = env::temp_dir().join("filecmp_dirs_equal_example");
+/// for dir in &["a", "b"] {
+///     fs::create_dir_all(temp_dir.join(dir).join("sub")).unwrap();
+///     fs::write(temp_dir.join(dir).join("sub").join("file.txt"), b"same").unwrap();
+/// }
+///
+/// let options = CmpOptions::new().shallow(false);
+/// assert!(dirs_equal(temp_dir.join("a"), temp_dir.join("b"), &options).unwrap());
+/// ```
+pub fn dirs_equal(
+    a: impl AsRef<Path>,
+    b: impl AsRef<Path>,
+    options: &CmpOptions,
+) -> io::Result<bool> {
+    DirCmpBuilder::new(a, b)
+        .cmp_options(options.clone())
+        .equal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemFs;
+    use crate::NameMatching;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_differences() {
+        let mut left = MemFs::new("left");
+        left.add_file("same.txt", "same");
+        left.add_file("a/diff.txt", "left");
+        left.add_file("a/b/only_left.txt", "left");
+        left.add_file("kind", "file");
+        left.add_special("pipe", crate::stat::S_IFIFO);
+        let mut right = MemFs::new("right");
+        right.add_file("same.txt", "same");
+        right.add_file("a/diff.txt", "right");
+        right.add_dir("a/b");
+        right.add_dir("kind");
+        right.add_special("pipe", crate::stat::S_IFIFO);
+        right.add_file("z_only_right.txt", "right");
+        let builder = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .shallow(false);
+
+        let differences: Vec<Difference> = builder.differences().map(Result::unwrap).collect();
+        let d = |path: &str, kind| Difference {
+            path: PathBuf::from(path),
+            kind,
+        };
+        assert_eq!(
+            differences,
+            vec![
+                d("z_only_right.txt", DifferenceKind::RightOnly),
+                d("pipe", DifferenceKind::Funny),
+                d("kind", DifferenceKind::Funny),
+                d("a/diff.txt", DifferenceKind::Different),
+                d("a/b/only_left.txt", DifferenceKind::LeftOnly),
+            ]
+        );
+
+        let first = builder.differences().next().unwrap().unwrap();
+        assert_eq!(first.path, Path::new("z_only_right.txt"));
+        assert!(compare_trees("/nonexistent/left", "/nonexistent/right")
+            .next()
+            .unwrap()
+            .is_err());
+
+        let mut left = MemFs::new("left");
+        left.add_file("File", "same");
+        left.add_file("Sub/f", "left");
+        let mut right = MemFs::new("right");
+        right.add_file("file", "same");
+        right.add_file("sub/f", "right");
+        let differences: Vec<Difference> = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .shallow(false)
+            .name_matching(NameMatching::CaseInsensitive)
+            .differences()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(differences, vec![d("Sub/f", DifferenceKind::Different)]);
+    }
+
+    #[test]
+    fn test_dirs_equal() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_dirs_equal");
+        let _ = fs::remove_dir_all(&test_dir);
+        let (a, b) = (test_dir.join("a"), test_dir.join("b"));
+        for dir in &[&a, &b] {
+            fs::create_dir_all(dir.join("sub")).unwrap();
+            fs::write(dir.join("sub").join("file.txt"), b"same").unwrap();
+        }
+        let options = CmpOptions::new().shallow(false);
+        assert!(dirs_equal(&a, &b, &options).unwrap());
+
+        fs::write(b.join("sub").join("file.txt"), b"diff").unwrap();
+        assert!(!dirs_equal(&a, &b, &options).unwrap());
+        fs::write(b.join("sub").join("file.txt"), b"same").unwrap();
+        fs::create_dir(a.join("empty")).unwrap();
+        assert!(!dirs_equal(&a, &b, &options).unwrap());
+        assert!(DirCmpBuilder::new(&a, &b)
+            .ignore(vec!["empty"])
+            .shallow(false)
+            .equal()
+            .unwrap());
+        assert!(dirs_equal(&a, test_dir.join("missing"), &options).is_err());
+
+        let (c, d) = (test_dir.join("c"), test_dir.join("d"));
+        fs::create_dir_all(c.join("Sub")).unwrap();
+        fs::create_dir_all(d.join("sub")).unwrap();
+        fs::write(c.join("File"), b"same").unwrap();
+        fs::write(d.join("file"), b"same").unwrap();
+        fs::write(c.join("Sub").join("f"), b"same").unwrap();
+        fs::write(d.join("sub").join("f"), b"same").unwrap();
+        let builder = DirCmpBuilder::new(&c, &d)
+            .shallow(false)
+            .name_matching(NameMatching::CaseInsensitive);
+        assert!(builder.equal().unwrap());
+        fs::write(d.join("sub").join("f"), b"diff").unwrap();
+        assert!(!builder.equal().unwrap());
+    }
+}
@@ -0,0 +1,161 @@
+//! Content digests of files.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use blake2::digest::VariableOutput;
+use blake2::Blake2bVar;
+use sha2::{Digest, Sha256};
+
+use crate::{os, xattr, BUFSIZE};
+
+/// Extended attribute caching the SHA-256 digest of a file, see
+/// `CmpOptions::digest_cache()`. Its value is the size, mtime and inode of
+/// the file when it was digested, then the digest, separated by spaces.
+pub const CACHE_XATTR: &str = "user.filecmp.sha256";
+
+/// Return the SHA-256 digest of everything `reader` yields, in lowercase
+/// hex.
+pub fn sha256(reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    read_all(reader, |buf| hasher.update(buf))?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Return the SHA-256 digest of a file like `sha256()`, trusting the one
+/// cached in CACHE_XATTR while the size, mtime and inode of the file are
+/// unchanged, and caching it otherwise.
+///
+/// Files whose attributes cannot be read or written are digested every
+/// time.
+pub fn cached_sha256(path: &Path) -> io::Result<String> {
+    let stamp = |st: os::StatResult| format!("{} {} {}", st.st_size, st.st_mtime, st.st_ino);
+    let before = stamp(os::stat(path, true)?);
+    if let Ok(Some(value)) = xattr::get(path, CACHE_XATTR) {
+        let cached = String::from_utf8(value).ok().and_then(|value| {
+            let (cached_stamp, digest) = value.rsplit_once(' ')?;
+            (cached_stamp == before).then(|| digest.to_string())
+        });
+        if let Some(digest) = cached {
+            return Ok(digest);
+        }
+    }
+    let digest = sha256(File::open(path)?)?;
+    // do not cache the digest of a file changed while reading it
+    if stamp(os::stat(path, true)?) == before {
+        let value = format!("{} {}", before, digest);
+        let _ = xattr::set(path, CACHE_XATTR, value.as_bytes());
+    }
+    Ok(digest)
+}
+
+/// Return the BLAKE2b digest of `len` bytes (1 to 64, 64 for `b2sum`) of
+/// everything `reader` yields, in lowercase hex. Other lengths fail with
+/// `InvalidInput`.
+pub fn blake2b(reader: impl Read, len: usize) -> io::Result<String> {
+    // the same method name as Digest::update of sha2
+    use blake2::digest::Update;
+
+    let mut hasher = Some(len)
+        .filter(|len| (1..=64).contains(len))
+        .and_then(|len| Blake2bVar::new(len).ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "BLAKE2b digests have 1 to 64 bytes",
+            )
+        })?;
+    read_all(reader, |buf| hasher.update(buf))?;
+    let mut out = vec![0; len];
+    hasher
+        .finalize_variable(&mut out)
+        .expect("output buffer of the digest length");
+    Ok(hex(&out))
+}
+
+/// Feed everything `reader` yields to `update`, BUFSIZE bytes at a time.
+fn read_all(mut reader: impl Read, mut update: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buf = [0; BUFSIZE];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        update(&buf[..n]);
+    }
+}
+
+/// Format bytes in lowercase hex.
+pub fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(s, "{:02x}", b);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_cached_sha256() {
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_cached_sha256");
+        fs::create_dir_all(&test_dir).unwrap();
+        let path = test_dir.join("file");
+        fs::write(&path, "abc").unwrap();
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(cached_sha256(&path).unwrap(), abc);
+
+        // file systems without user attributes only miss the cache
+        let value = match xattr::get(&path, CACHE_XATTR).unwrap() {
+            Some(value) => String::from_utf8(value).unwrap(),
+            None => return,
+        };
+        assert!(value.ends_with(abc));
+        let forged = value.replace(abc, "forged");
+        xattr::set(&path, CACHE_XATTR, forged.as_bytes()).unwrap();
+        assert_eq!(cached_sha256(&path).unwrap(), "forged");
+
+        fs::write(&path, "abcd").unwrap();
+        assert_eq!(
+            cached_sha256(&path).unwrap(),
+            "88d4266fd4e6338d13b845fcf289579d209c897823b9217da3e161936f031589"
+        );
+    }
+
+    #[test]
+    fn test_blake2b() {
+        // RFC 7693 appendix A and `b2sum`
+        assert_eq!(
+            blake2b(&b"abc"[..], 64).unwrap(),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+        assert_eq!(
+            blake2b(&b""[..], 32).unwrap(),
+            "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+        );
+        // block boundaries
+        let xs = [b'x'; 129];
+        assert_eq!(
+            blake2b(&xs[..128], 64).unwrap(),
+            "082b91ea2e15d1556d2ceefdd5af5d64d31b4e01aff1959724578876293825b2\
+             36ee8079173a0a38160d7d6685d6bca0bfb62c177b3599b8727d9173e2115b91"
+        );
+        assert_eq!(
+            blake2b(&xs[..], 32).unwrap(),
+            "03b0758fa71d249c846c2304a2b9996e6ae66ffa7e5528f9e612e089a360fc8d"
+        );
+        assert!(blake2b(&xs[..], 0).is_err());
+        assert!(blake2b(&xs[..], 65).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Shared extent detection on Linux, using the FIEMAP ioctl.
+//!
+//! Reflinked copies on btrfs or XFS map their logical ranges to the same
+//! physical extents as the original, so they are identical without reading
+//! them. Files whose extents are not all shared, or file systems without
+//! FIEMAP, fall back to content comparison.
+
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+
+/// _IOWR('f', 11, struct fiemap), where struct fiemap has no extents.
+const FS_IOC_FIEMAP: libc::c_ulong = iowr(
+    b'f',
+    11,
+    mem::size_of::<Fiemap>() - mem::size_of::<[FiemapExtent; EXTENT_COUNT]>(),
+);
+const FIEMAP_FLAG_SYNC: u32 = 0x0001;
+const FIEMAP_EXTENT_LAST: u32 = 0x0001;
+const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+/// Extents whose physical location does not tell where the data is:
+/// unknown, delayed allocation, encoded, encrypted, not aligned, inline
+/// and tail-packed.
+const FIEMAP_EXTENT_UNRELIABLE: u32 = 0x0002 | 0x0004 | 0x0008 | 0x0080 | 0x0100 | 0x0200 | 0x0400;
+/// Number of extents fetched per ioctl call.
+const EXTENT_COUNT: usize = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FiemapExtent {
+    fe_logical: u64,
+    fe_physical: u64,
+    fe_length: u64,
+    fe_reserved64: [u64; 2],
+    fe_flags: u32,
+    fe_reserved: [u32; 3],
+}
+
+#[repr(C)]
+struct Fiemap {
+    fm_start: u64,
+    fm_length: u64,
+    fm_flags: u32,
+    fm_mapped_extents: u32,
+    fm_extent_count: u32,
+    fm_reserved: u32,
+    fm_extents: [FiemapExtent; EXTENT_COUNT],
+}
+
+/// The bits of the direction and size fields of ioctl numbers, which
+/// differ from the generic ones on a few architectures.
+#[cfg(any(
+    target_arch = "mips",
+    target_arch = "mips32r6",
+    target_arch = "mips64",
+    target_arch = "mips64r6",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc",
+    target_arch = "sparc64"
+))]
+const IOC_SIZEBITS_READ_WRITE: (u32, libc::c_ulong) = (13, 2 | 4);
+#[cfg(not(any(
+    target_arch = "mips",
+    target_arch = "mips32r6",
+    target_arch = "mips64",
+    target_arch = "mips64r6",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc",
+    target_arch = "sparc64"
+)))]
+const IOC_SIZEBITS_READ_WRITE: (u32, libc::c_ulong) = (14, 1 | 2);
+
+/// The _IOWR() macro of the kernel.
+const fn iowr(ty: u8, nr: u8, size: usize) -> libc::c_ulong {
+    let (sizebits, read_write) = IOC_SIZEBITS_READ_WRITE;
+    read_write << (16 + sizebits)
+        | (size as libc::c_ulong) << 16
+        | (ty as libc::c_ulong) << 8
+        | nr as libc::c_ulong
+}
+
+/// A mapped range: logical offset, physical offset and length.
+type Extent = (u64, u64, u64);
+
+/// Whether two files share all their extents.
+///
+/// Returns false whenever it cannot tell, including when the files are
+/// identical copies in separate extents.
+pub fn same_extents(f1: &File, f2: &File) -> bool {
+    match (extents(f1), extents(f2)) {
+        (Ok(Some(e1)), Ok(Some(e2))) => !e1.is_empty() && e1 == e2,
+        _ => false,
+    }
+}
+
+/// The merged extents of a file, or None if any of them is not shared or
+/// not reliably placed.
+fn extents(file: &File) -> io::Result<Option<Vec<Extent>>> {
+    let mut extents: Vec<Extent> = Vec::new();
+    let mut start = 0;
+    loop {
+        let mut fiemap = Fiemap {
+            fm_start: start,
+            fm_length: u64::MAX - start,
+            fm_flags: FIEMAP_FLAG_SYNC,
+            fm_mapped_extents: 0,
+            fm_extent_count: EXTENT_COUNT as u32,
+            fm_reserved: 0,
+            fm_extents: [FiemapExtent::default(); EXTENT_COUNT],
+        };
+        let ret = unsafe {
+            libc::ioctl(
+                file.as_raw_fd(),
+                FS_IOC_FIEMAP as _,
+                &mut fiemap as *mut Fiemap,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mapped = &fiemap.fm_extents[..fiemap.fm_mapped_extents as usize];
+        for extent in mapped {
+            if extent.fe_flags & FIEMAP_EXTENT_UNRELIABLE != 0
+                || extent.fe_flags & FIEMAP_EXTENT_SHARED == 0
+            {
+                return Ok(None);
+            }
+            push_merged(
+                &mut extents,
+                (extent.fe_logical, extent.fe_physical, extent.fe_length),
+            );
+        }
+        match mapped.last() {
+            Some(last) if last.fe_flags & FIEMAP_EXTENT_LAST == 0 => {
+                start = last.fe_logical + last.fe_length;
+            }
+            _ => return Ok(Some(extents)),
+        }
+    }
+}
+
+/// Append an extent, merging it with the previous one when both are
+/// contiguous logically and physically, since file systems may split the
+/// same mapping differently for two files.
+fn push_merged(extents: &mut Vec<Extent>, extent: Extent) {
+    if let Some(prev) = extents.last_mut() {
+        if prev.0 + prev.2 == extent.0 && prev.1 + prev.2 == extent.1 {
+            prev.2 += extent.2;
+            return;
+        }
+    }
+    extents.push(extent);
+}
+
+const _: () = assert!(mem::size_of::<FiemapExtent>() == 56);
+#[cfg(target_arch = "x86_64")]
+const _: () = assert!(FS_IOC_FIEMAP == 0xc020_660b);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_push_merged() {
+        let mut extents = Vec::new();
+        push_merged(&mut extents, (0, 4096, 4096));
+        push_merged(&mut extents, (4096, 8192, 4096));
+        push_merged(&mut extents, (8192, 65536, 4096));
+        push_merged(&mut extents, (16384, 69632, 4096));
+        assert_eq!(
+            extents,
+            vec![(0, 4096, 8192), (8192, 65536, 4096), (16384, 69632, 4096)]
+        );
+    }
+
+    #[test]
+    fn test_same_extents_copies() {
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_same_extents");
+        fs::create_dir_all(&test_dir).unwrap();
+        fs::write(test_dir.join("a.txt"), b"separate copies").unwrap();
+        fs::write(test_dir.join("b.txt"), b"separate copies").unwrap();
+        let a = File::open(test_dir.join("a.txt")).unwrap();
+        let b = File::open(test_dir.join("b.txt")).unwrap();
+        assert!(!same_extents(&a, &b));
+    }
+}
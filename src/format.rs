@@ -0,0 +1,694 @@
+//! Output formats of a `DirCmp`, for people and for other programs.
+//!
+//! Every format is rendered from the same `DirCmp`: `text` is the report of
+//! filecmp.py, the others are built on `DirCmp::entries()`.
+
+#[cfg(feature = "serde")]
+use std::borrow::Cow;
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::{DirCmp, MetaDiff, ReportDisplay, XattrDiff};
+
+/// An output format of `DirCmp::display()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The report of filecmp.py, as printed by `DirCmp::report()`.
+    Text,
+    /// One JSON document listing the roots and all entries. Needs the
+    /// `serde` feature.
+    #[cfg(feature = "serde")]
+    Json,
+    /// One JSON object per entry and line. Needs the `serde` feature.
+    #[cfg(feature = "serde")]
+    Ndjson,
+    /// The differences as an indented tree, leaving out identical files.
+    /// Entries are marked `-` left only, `+` right only, `~` different,
+    /// `!` funny or trouble and `*` same with other metadata, attributes
+    /// or right name. Moves follow the tree, marked `>`.
+    Tree,
+    /// A `path,status,metadata,right_path,similarity` header, then one
+    /// line per entry.
+    Csv,
+}
+
+impl Format {
+    /// Names of all formats, as accepted by `from_str()`.
+    #[cfg(feature = "serde")]
+    pub const NAMES: [&'static str; 5] = ["text", "json", "ndjson", "tree", "csv"];
+    /// Names of all formats, as accepted by `from_str()`.
+    #[cfg(not(feature = "serde"))]
+    pub const NAMES: [&'static str; 3] = ["text", "tree", "csv"];
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            #[cfg(feature = "serde")]
+            "json" => Ok(Format::Json),
+            #[cfg(feature = "serde")]
+            "ndjson" => Ok(Format::Ndjson),
+            #[cfg(not(feature = "serde"))]
+            "json" | "ndjson" => Err(format!("format '{}' needs the serde feature", s)),
+            "tree" => Ok(Format::Tree),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+/// How an entry compares, after the list of `DirCmp` holding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// In `left_only`.
+    LeftOnly,
+    /// In `right_only`.
+    RightOnly,
+    /// In `same_files`.
+    Same,
+    /// In `diff_files`.
+    Different,
+    /// In `funny_files`: could not be compared.
+    Trouble,
+    /// In `common_funny`: of another type in each tree, or not stat-able.
+    Funny,
+    /// In `common_dirs`.
+    Dir,
+    /// In `same_dirs`: not walked, with equal Merkle digests.
+    IdenticalDir,
+    /// In `moves`.
+    Moved,
+}
+
+impl Status {
+    /// Marker of the status in the tree format, where identical files
+    /// only show up with metadata differences.
+    pub fn marker(self) -> char {
+        match self {
+            Status::LeftOnly => '-',
+            Status::RightOnly => '+',
+            Status::Same => '*',
+            Status::Dir | Status::IdenticalDir => ' ',
+            Status::Different => '~',
+            Status::Trouble | Status::Funny => '!',
+            Status::Moved => '>',
+        }
+    }
+
+    /// ANSI SGR color code of the status in the tree format.
+    fn color(self) -> &'static str {
+        match self {
+            Status::LeftOnly => "31",
+            Status::RightOnly => "32",
+            Status::Same => "36",
+            Status::Different => "33",
+            Status::Trouble | Status::Funny => "35",
+            Status::Dir | Status::IdenticalDir => "1;34",
+            Status::Moved => "34",
+        }
+    }
+
+    /// Name of the status in machine-readable formats.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::LeftOnly => "left_only",
+            Status::RightOnly => "right_only",
+            Status::Same => "same",
+            Status::Different => "different",
+            Status::Trouble => "trouble",
+            Status::Funny => "funny",
+            Status::Dir => "dir",
+            Status::IdenticalDir => "identical_dir",
+            Status::Moved => "moved",
+        }
+    }
+}
+
+/// An entry of `DirCmp::entries()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Path relative to the roots of both trees, or to the left root if
+    /// `right_path` is set.
+    pub path: PathBuf,
+    pub status: Status,
+    /// Metadata differences, from `DirCmp::meta_diffs`.
+    pub meta: Option<MetaDiff>,
+    /// Extended attribute differences, from `DirCmp::xattr_diffs`.
+    pub xattrs: Option<XattrDiff>,
+    /// Path relative to the right root, if it differs from `path`: by
+    /// the names of `DirCmp::name_pairs`, or as the target of a move.
+    pub right_path: Option<PathBuf>,
+    /// Share of lines in common of a move, see `Move::similarity`.
+    pub similarity: Option<f64>,
+}
+
+impl DirCmp {
+    /// All names of the comparison, sorted, each followed by the entries
+    /// of its subdirectory if `recursive`, then the moves.
+    pub fn entries(&self, recursive: bool) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        self.collect_entries(Path::new(""), Path::new(""), recursive, &mut entries);
+        entries.extend(self.moves.iter().map(|m| Entry {
+            path: m.from.clone(),
+            status: Status::Moved,
+            meta: None,
+            xattrs: None,
+            right_path: Some(m.to.clone()),
+            similarity: Some(m.similarity),
+        }));
+        entries
+    }
+
+    fn collect_entries(&self, rel: &Path, right_rel: &Path, recursive: bool, out: &mut Vec<Entry>) {
+        for (name, status) in level(self) {
+            let path = rel.join(name);
+            let right_path = right_rel.join(self.right_name(name));
+            out.push(Entry {
+                path: path.clone(),
+                status,
+                meta: self.meta_diffs.get(name).copied(),
+                xattrs: self.xattr_diffs.get(name).cloned(),
+                right_path: Some(right_path.clone())
+                    .filter(|right| status != Status::LeftOnly && *right != path),
+                similarity: None,
+            });
+            if recursive && status == Status::Dir {
+                if let Some(sd) = self.subdirs.get(name) {
+                    sd.collect_entries(&path, &right_path, recursive, out);
+                }
+            }
+        }
+    }
+
+    /// Render the comparison in `format`, with subdirectories if
+    /// `recursive`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::env;
+    /// use std::fs;
+    /// use filecmp::format::Format;
+    /// use filecmp::DirCmp;
+    ///
+    /// let temp_dir = env::temp_dir().join("filecmp_display_example");
+    /// fs::create_dir_all(temp_dir.join("a")).unwrap();
+    /// fs::create_dir_all(temp_dir.join("b")).unwrap();
+    /// fs::write(temp_dir.join("a").join("only_a.txt"), b"a").unwrap();
+    ///
+    /// let x = DirCmp::new(temp_dir.join("a"), temp_dir.join("b")).unwrap();
+    /// let csv = x.display(Format::Csv, true).to_string();
+    /// assert_eq!(
+    ///     csv,
+    ///     "path,status,metadata,right_path,similarity\nonly_a.txt,left_only,,,\n"
+    /// );
+    /// ```
+    pub fn display(&self, format: Format, recursive: bool) -> Report<'_> {
+        Report {
+            dd: self,
+            format,
+            recursive,
+            color: false,
+        }
+    }
+
+    /// Print the differences of self and subdirs as a tree, in colors if
+    /// `use_color()` allows.
+    pub fn report_tree(&self) {
+        print!("{}", self.display(Format::Tree, true).color(use_color()));
+    }
+}
+
+/// A `DirCmp` rendered in some format, see `DirCmp::display()`.
+pub struct Report<'a> {
+    dd: &'a DirCmp,
+    format: Format,
+    recursive: bool,
+    color: bool,
+}
+
+impl Report<'_> {
+    /// Color the tree format with ANSI escape codes. Off by default.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+/// Whether to color output on stdout: it must be a terminal, and the
+/// `NO_COLOR` environment variable must be unset or empty.
+pub fn use_color() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty());
+    !no_color && io::stdout().is_terminal()
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dd = self.dd;
+        match self.format {
+            Format::Text => text(f, dd, self.recursive, true),
+            #[cfg(feature = "serde")]
+            Format::Json => {
+                let entries = dd.entries(self.recursive);
+                let report = JsonReport {
+                    left: dd.left.to_string_lossy(),
+                    right: dd.right.to_string_lossy(),
+                    entries: entries.iter().map(JsonEntry::from).collect(),
+                };
+                let json = serde_json::to_string_pretty(&report).map_err(|_| fmt::Error)?;
+                writeln!(f, "{}", json)
+            }
+            #[cfg(feature = "serde")]
+            Format::Ndjson => {
+                for entry in dd.entries(self.recursive) {
+                    let json =
+                        serde_json::to_string(&JsonEntry::from(&entry)).map_err(|_| fmt::Error)?;
+                    writeln!(f, "{}", json)?;
+                }
+                Ok(())
+            }
+            Format::Tree => {
+                writeln!(f, "diff {} {}", dd.left.display(), dd.right.display())?;
+                tree(f, dd, "", self)?;
+                for m in &dd.moves {
+                    let line = format!(
+                        "{} {} -> {} ({:.0}%)",
+                        Status::Moved.marker(),
+                        m.from.display(),
+                        m.to.display(),
+                        m.similarity * 100.0
+                    );
+                    writeln!(f, "{}", paint(self, Status::Moved, &line))?;
+                }
+                Ok(())
+            }
+            Format::Csv => {
+                writeln!(f, "path,status,metadata,right_path,similarity")?;
+                for entry in dd.entries(self.recursive) {
+                    let right_path = entry.right_path.as_ref().map(|p| p.to_string_lossy());
+                    writeln!(
+                        f,
+                        "{},{},{},{},{}",
+                        csv_field(&entry.path.to_string_lossy()),
+                        entry.status.as_str(),
+                        meta_fields(entry.meta.as_ref(), entry.xattrs.as_ref()).join(" "),
+                        csv_field(right_path.as_deref().unwrap_or_default()),
+                        entry.similarity.map(|x| x.to_string()).unwrap_or_default()
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The names of one directory level with their status, sorted.
+fn level(dd: &DirCmp) -> Vec<(&PathBuf, Status)> {
+    let lists = [
+        (&dd.left_only, Status::LeftOnly),
+        (&dd.right_only, Status::RightOnly),
+        (&dd.same_files, Status::Same),
+        (&dd.diff_files, Status::Different),
+        (&dd.funny_files, Status::Trouble),
+        (&dd.common_funny, Status::Funny),
+        (&dd.common_dirs, Status::Dir),
+    ];
+    let mut names: Vec<(&PathBuf, Status)> = lists
+        .iter()
+        .flat_map(|(list, status)| list.iter().map(move |x| (x, *status)))
+        .map(|(x, status)| match status {
+            Status::Dir if dd.same_dirs.contains(x) => (x, Status::IdenticalDir),
+            _ => (x, status),
+        })
+        .collect();
+    names.sort_by(|a, b| a.0.cmp(b.0));
+    names
+}
+
+/// Whether anything differs below a comparison, counting paired names.
+fn has_differences(dd: &DirCmp) -> bool {
+    !dd.meta_diffs.is_empty()
+        || !dd.xattr_diffs.is_empty()
+        || !dd.name_pairs.is_empty()
+        || level(dd).iter().any(|(name, status)| match status {
+            Status::Same | Status::IdenticalDir => false,
+            Status::Dir => dd.subdirs.get(*name).is_some_and(has_differences),
+            _ => true,
+        })
+}
+
+/// Names of the differing fields of a `MetaDiff`, then `xattrs` if
+/// extended attributes differ.
+fn meta_fields(meta: Option<&MetaDiff>, xattrs: Option<&XattrDiff>) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if let Some(meta) = meta {
+        if meta.mode.is_some() {
+            fields.push("mode");
+        }
+        if meta.mtime.is_some() {
+            fields.push("mtime");
+        }
+        if meta.uid.is_some() {
+            fields.push("uid");
+        }
+        if meta.gid.is_some() {
+            fields.push("gid");
+        }
+    }
+    if xattrs.is_some() {
+        fields.push("xattrs");
+    }
+    fields
+}
+
+/// The filecmp.py report, followed by those of subdirectories like
+/// `report_full_closure()`.
+fn text(f: &mut fmt::Formatter<'_>, dd: &DirCmp, recursive: bool, first: bool) -> fmt::Result {
+    if !first {
+        writeln!(f)?;
+    }
+    write!(f, "{}", ReportDisplay(dd))?;
+    if recursive {
+        for sd in dd.subdirs.values() {
+            text(f, sd, recursive, false)?;
+        }
+    }
+    Ok(())
+}
+
+/// Draw the differing entries of `dd`, and the subdirectories holding
+/// some, below a line starting with `prefix`. Right names of paired
+/// entries follow an arrow, metadata and attribute differences follow in
+/// parentheses, with the `*` marker on identical files.
+fn tree(f: &mut fmt::Formatter<'_>, dd: &DirCmp, prefix: &str, report: &Report) -> fmt::Result {
+    let shown: Vec<(&PathBuf, Status, Option<&DirCmp>)> = level(dd)
+        .into_iter()
+        .filter_map(|(name, status)| {
+            let meta = dd.meta_diffs.contains_key(name)
+                || dd.xattr_diffs.contains_key(name)
+                || dd.name_pairs.contains_key(name);
+            match status {
+                Status::Same | Status::IdenticalDir if !meta => None,
+                Status::Dir => {
+                    let sd = dd.subdirs.get(name);
+                    let walk = report.recursive && sd.is_some_and(has_differences);
+                    if walk || meta {
+                        Some((name, status, sd.filter(|_| walk)))
+                    } else {
+                        None
+                    }
+                }
+                _ => Some((name, status, None)),
+            }
+        })
+        .collect();
+    for (i, (name, status, sd)) in shown.iter().enumerate() {
+        let last = i + 1 == shown.len();
+        let branch = if last { "└── " } else { "├── " };
+        let mut line = match status {
+            Status::Dir | Status::IdenticalDir => format!("{}/", name.display()),
+            _ => format!("{} {}", status.marker(), name.display()),
+        };
+        if let Some(right) = dd.name_pairs.get(*name) {
+            line = format!("{} -> {}", line, right.display());
+        }
+        let fields = meta_fields(dd.meta_diffs.get(*name), dd.xattr_diffs.get(*name));
+        if !fields.is_empty() {
+            line = format!("{} ({})", line, fields.join(", "));
+        }
+        writeln!(f, "{}{}{}", prefix, branch, paint(report, *status, &line))?;
+        if let Some(sd) = sd {
+            let indent = if last { "    " } else { "│   " };
+            tree(f, sd, &format!("{}{}", prefix, indent), report)?;
+        }
+    }
+    Ok(())
+}
+
+/// Wrap `text` in the color of `status` if the report is colored.
+fn paint(report: &Report, status: Status, text: &str) -> String {
+    if report.color {
+        format!("\x1b[{}m{}\x1b[0m", status.color(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A report in the JSON format.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonReport<'a> {
+    left: Cow<'a, str>,
+    right: Cow<'a, str>,
+    entries: Vec<JsonEntry<'a>>,
+}
+
+/// An entry in the JSON formats, with paths and attribute names which
+/// are not UTF-8 replaced.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonEntry<'a> {
+    path: Cow<'a, str>,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    right_path: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    similarity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a MetaDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xattrs: Option<JsonXattrs<'a>>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonXattrs<'a> {
+    added: Vec<Cow<'a, str>>,
+    removed: Vec<Cow<'a, str>>,
+    changed: Vec<Cow<'a, str>>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> From<&'a Entry> for JsonEntry<'a> {
+    fn from(entry: &'a Entry) -> Self {
+        let names =
+            |names: &'a [std::ffi::OsString]| names.iter().map(|x| x.to_string_lossy()).collect();
+        JsonEntry {
+            path: entry.path.to_string_lossy(),
+            status: entry.status.as_str(),
+            right_path: entry.right_path.as_ref().map(|p| p.to_string_lossy()),
+            similarity: entry.similarity,
+            metadata: entry.meta.as_ref(),
+            xattrs: entry.xattrs.as_ref().map(|xattrs| JsonXattrs {
+                added: names(&xattrs.added),
+                removed: names(&xattrs.removed),
+                changed: names(&xattrs.changed),
+            }),
+        }
+    }
+}
+
+/// Quote a CSV field if needed, as in RFC 4180.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemFs;
+    use crate::{DirCmpBuilder, NameMatching};
+
+    #[test]
+    fn test_formats() {
+        let mut left = MemFs::new("left");
+        left.add_file("same.txt", "same");
+        left.add_file("sub/diff.txt", "left");
+        left.add_file("sub/ok.txt", "ok");
+        left.add_file("equal/ok.txt", "ok");
+        left.add_file("a,\"b\".txt", "left");
+        let mut right = MemFs::new("right");
+        right.add_file("same.txt", "same");
+        right.add_file("sub/diff.txt", "right");
+        right.add_file("sub/ok.txt", "ok");
+        right.add_file("equal/ok.txt", "ok");
+        right.add_dir("sub/new");
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .shallow(false)
+            .build()
+            .unwrap();
+
+        let entries = x.entries(true);
+        let statuses: Vec<(&str, Status)> = entries
+            .iter()
+            .map(|e| (e.path.to_str().unwrap(), e.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("a,\"b\".txt", Status::LeftOnly),
+                ("equal", Status::Dir),
+                ("equal/ok.txt", Status::Same),
+                ("same.txt", Status::Same),
+                ("sub", Status::Dir),
+                ("sub/diff.txt", Status::Different),
+                ("sub/new", Status::RightOnly),
+                ("sub/ok.txt", Status::Same),
+            ]
+        );
+        assert_eq!(x.entries(false).len(), 4);
+
+        assert_eq!(
+            x.display(Format::Tree, true).to_string(),
+            "diff left right\n\
+             ├── - a,\"b\".txt\n\
+             └── sub/\n    \
+                 ├── ~ diff.txt\n    \
+                 └── + new\n"
+        );
+        let colored = x.display(Format::Tree, true).color(true).to_string();
+        assert!(colored.contains("├── \x1b[31m- a,\"b\".txt\x1b[0m\n"));
+        assert!(colored.contains("└── \x1b[1;34msub/\x1b[0m\n"));
+        let csv = x.display(Format::Csv, false).to_string();
+        assert!(csv.starts_with(
+            "path,status,metadata,right_path,similarity\n\"a,\"\"b\"\".txt\",left_only,,,\n"
+        ));
+        #[cfg(feature = "serde")]
+        {
+            let ndjson = x.display(Format::Ndjson, true).to_string();
+            assert_eq!(ndjson.lines().count(), 8);
+            assert!(ndjson.contains("{\"path\":\"a,\\\"b\\\".txt\",\"status\":\"left_only\"}\n"));
+            let json = x.display(Format::Json, false).to_string();
+            let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(json["left"], "left");
+            assert_eq!(json["right"], "right");
+            assert_eq!(json["entries"].as_array().unwrap().len(), 4);
+            assert_eq!(
+                json["entries"][3],
+                serde_json::json!({"path": "sub", "status": "dir"})
+            );
+        }
+        let text = x.display(Format::Text, true).to_string();
+        assert!(text.contains("\ndiff left/sub right/sub\n"));
+
+        let mut left = MemFs::new("left");
+        left.add_file("same.txt", "same").mode(0o600);
+        let mut right = MemFs::new("right");
+        right.add_file("same.txt", "same");
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .shallow(false)
+            .metadata(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            x.display(Format::Tree, true).to_string(),
+            "diff left right\n└── * same.txt (mode)\n"
+        );
+        #[cfg(feature = "serde")]
+        {
+            let ndjson = x.display(Format::Ndjson, true).to_string();
+            let entry: serde_json::Value = serde_json::from_str(&ndjson).unwrap();
+            assert_eq!(entry["status"], "same");
+            let mode = x.meta_diffs[Path::new("same.txt")].mode.unwrap();
+            assert_eq!(mode.0, 0o600);
+            assert_eq!(
+                entry["metadata"],
+                serde_json::json!({"mode": [mode.0, mode.1]})
+            );
+        }
+
+        // identical directories, moves and paired names
+        let mut left = MemFs::new("left");
+        left.add_file("equal/ok.txt", "ok");
+        left.add_file("old.txt", "moved");
+        left.add_file("Sub/ok.txt", "ok");
+        let mut right = MemFs::new("right");
+        right.add_file("equal/ok.txt", "ok");
+        right.add_file("new.txt", "moved");
+        right.add_file("sub/ok.txt", "ok");
+        right.add_file("sub/other.txt", "other");
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .merkle(true)
+            .renames(true)
+            .name_matching(NameMatching::CaseInsensitive)
+            .build()
+            .unwrap();
+        let entries = x.entries(true);
+        let statuses: Vec<(&str, Status)> = entries
+            .iter()
+            .map(|e| (e.path.to_str().unwrap(), e.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("Sub", Status::Dir),
+                ("Sub/ok.txt", Status::Same),
+                ("Sub/other.txt", Status::RightOnly),
+                ("equal", Status::IdenticalDir),
+                ("old.txt", Status::Moved),
+            ]
+        );
+        assert_eq!(
+            x.display(Format::Tree, true).to_string(),
+            "diff left right\n\
+             └── Sub/ -> sub\n    \
+                 └── + other.txt\n\
+             > old.txt -> new.txt (100%)\n"
+        );
+        let csv = x.display(Format::Csv, true).to_string();
+        assert!(csv.contains("\nequal,identical_dir,,,\n"));
+        assert!(csv.contains("\nSub/other.txt,right_only,,sub/other.txt,\n"));
+        assert!(csv.ends_with("\nold.txt,moved,,new.txt,1\n"));
+        #[cfg(feature = "serde")]
+        {
+            let ndjson = x.display(Format::Ndjson, true).to_string();
+            assert!(
+                ndjson.contains("{\"path\":\"Sub\",\"status\":\"dir\",\"right_path\":\"sub\"}\n")
+            );
+            assert!(ndjson.ends_with(
+                "{\"path\":\"old.txt\",\"status\":\"moved\",\"right_path\":\"new.txt\",\
+                 \"similarity\":1.0}\n"
+            ));
+        }
+
+        let mut left = MemFs::new("left");
+        left.add_file("attr.txt", "same").xattr("user.tag", "a");
+        let mut right = MemFs::new("right");
+        right.add_file("attr.txt", "same").xattr("user.tag", "b");
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .xattrs(["user.*"])
+            .build()
+            .unwrap();
+        assert_eq!(
+            x.display(Format::Tree, true).to_string(),
+            "diff left right\n└── ~ attr.txt (xattrs)\n"
+        );
+        #[cfg(feature = "serde")]
+        assert!(x.display(Format::Ndjson, true).to_string().contains(
+            "\"status\":\"different\",\"xattrs\":\
+             {\"added\":[],\"removed\":[],\"changed\":[\"user.tag\"]}}"
+        ));
+        assert_eq!("tree".parse(), Ok(Format::Tree));
+        assert!("xml".parse::<Format>().is_err());
+        #[cfg(not(feature = "serde"))]
+        assert!("json".parse::<Format>().unwrap_err().contains("serde"));
+    }
+}
@@ -1,21 +1,75 @@
-//! Utilities for comparing files and directories(WIP).
+//! Utilities for comparing files and directories.
 //!
 //! Struct like in Python3 std-lib:
-//!  - DirCmp
+//!  - DirCmp (built with DirCmpBuilder)
+//!
+//! Beyond it, `merge::MergeCmp` compares two directories against their
+//! common base (see `DirCmpBuilder::build_merge()`), and `multi::MultiCmp`
+//! compares any number of directories at once.
 //!
 //! Functions like in Python3 std-lib:
 //!  - cmp(f1, f2, shallow: bool) -> int
 //!  - cmpfiles(a, b, common) -> ([], [], [])
 //!  - clear_cache()
 //!
+//! Variants taking `CmpOptions` for finer control:
+//!  - cmp_with(f1, f2, options)
+//!  - cmpfiles_with(a, b, common, options)
+//!  - xattr_diff(f1, f2, options)
+//!
+//! Lazy iteration over the differences of two trees:
+//!  - compare_trees(a, b) -> impl Iterator<Item = io::Result<Difference>>
+//!  - dirs_equal(a, b, options) -> bool
+//!
+//! Trees can be read from other sources than the real file system through
+//! the `vfs` module. The `sync` module plans the operations making one
+//! directory match another from a `DirCmp`, and the `visit` module streams
+//! a comparison to callbacks instead of building a `DirCmp`. The `format`
+//! module renders a `DirCmp` as JSON, CSV or a tree, and the `manifest`
+//! module snapshots a tree's metadata and digests. The `mtree` module
+//! reads, writes and verifies BSD mtree(5) specifications, and the
+//! `checksum` module verifies `sha256sum` and `b2sum` files. The `merkle`
+//! module fingerprints whole trees. Asynchronous versions live in the
+//! `asyncio` module (feature `tokio`).
+//!
+//! The `filecmp` binary (default feature `cli`) compares two files
+//! or directories from the command line, exiting with 0, 1 or 2 for same,
+//! different or trouble like `cmp` and `diff -q`.
+//!
 //! # Example
 //!
 //! Check out [Example for cmp()](cmp#example)
 
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
+#[cfg(feature = "tokio")]
+pub mod asyncio;
+pub mod checksum;
+mod compress;
+mod diff;
+mod digest;
+#[cfg(target_os = "linux")]
+mod fiemap;
+pub mod format;
+pub mod manifest;
+pub mod merge;
+pub mod merkle;
+pub mod mtree;
+pub mod multi;
+mod names;
 mod os;
+mod rename;
+#[cfg(target_os = "linux")]
+mod sparse;
 mod stat;
+pub mod sync;
+pub mod vfs;
+pub mod visit;
+mod xattr;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
 use std::fs::File;
 use std::hash::{Hash, Hasher};
 use std::io::{self, Read};
@@ -24,13 +78,21 @@ use std::sync::{Arc, Mutex};
 
 use lazy_static::lazy_static;
 
+pub use diff::{compare_trees, dirs_equal, Difference, DifferenceKind, Differences};
+pub use names::NameMatching;
+pub use rename::Move;
+use vfs::{FileSystem, RealFs};
+pub use xattr::XattrDiff;
+
 const BUFSIZE: usize = 8 * 1024;
 const FOLLOW_SYMLINKS_DEFAULT: bool = true;
 const MAX_CACHE_SIZE: usize = 100;
 
+type CacheKey = (PathBuf, PathBuf, Signature, Signature, CmpOptions);
+
 lazy_static! {
     /// Cache for File Comparison
-    static ref CACHE: Arc<Mutex<HashMap<(PathBuf, PathBuf, Signature, Signature), bool>>> = Arc::new(Mutex::new(HashMap::new()));
+    static ref CACHE: Arc<Mutex<HashMap<CacheKey, bool>>> = Arc::new(Mutex::new(HashMap::new()));
 }
 
 /// Clear the filecmp cache.
@@ -78,45 +140,270 @@ pub fn clear_cache() {
 /// let a = filecmp::cmp(&foo_path, &bar_path, true).unwrap();
 /// let b = filecmp::cmp(&foo_path, &baz_path, true).unwrap();
 /// let c = filecmp::cmp(&bar_path, &baz_path, true).unwrap();
-/// 
+///
 /// assert!(a);
 /// assert!(!b);
 /// assert!(!c);
 /// ```
 ///
 pub fn cmp(f1: impl AsRef<Path>, f2: impl AsRef<Path>, shallow: bool) -> io::Result<bool> {
-    let s1 = sig(os::stat(f1.as_ref(), FOLLOW_SYMLINKS_DEFAULT)?);
-    let s2 = sig(os::stat(f2.as_ref(), FOLLOW_SYMLINKS_DEFAULT)?);
+    cmp_with(f1, f2, &CmpOptions::new().shallow(shallow))
+}
 
-    if s1.s_ifmt != stat::S_IFREG || s2.s_ifmt != stat::S_IFREG {
+/// Compare two files, with options beyond the shallow flag.
+///
+/// Arguments:
+///  - f1 -- First file name
+///  - f2 -- Second file name
+///  - options -- How to compare, see `CmpOptions`.
+///
+/// Return value:
+///  - True if the files are the same, False otherwise.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use filecmp::CmpOptions;
+///
+/// let options = CmpOptions::new().shallow(false).decompress(true);
+/// let same = filecmp::cmp_with("a.tar.gz", "b.tar.gz", &options).unwrap();
+/// ```
+pub fn cmp_with(
+    f1: impl AsRef<Path>,
+    f2: impl AsRef<Path>,
+    options: &CmpOptions,
+) -> io::Result<bool> {
+    cmp_in(&RealFs, f1.as_ref(), &RealFs, f2.as_ref(), options, true)
+}
+
+/// Compare two files through their file systems.
+///
+/// `local` tells that both files live on the real file system, which
+/// enables the cache for past comparisons and OS-specific fast paths.
+fn cmp_in(
+    fs1: &dyn FileSystem,
+    f1: &Path,
+    fs2: &dyn FileSystem,
+    f2: &Path,
+    options: &CmpOptions,
+    local: bool,
+) -> io::Result<bool> {
+    let s1 = sig(fs1.stat(f1, FOLLOW_SYMLINKS_DEFAULT)?);
+    let s2 = sig(fs2.stat(f2, FOLLOW_SYMLINKS_DEFAULT)?);
+
+    if !options.xattrs.is_empty() && !xattr_diff_in(fs1, f1, fs2, f2, &options.xattrs)?.is_empty() {
         return Ok(false);
     }
-    if shallow && s1 == s2 {
-        return Ok(true);
+    if let Some(outcome) = cmp_signatures(&s1, &s2, options.shallow) {
+        return Ok(outcome);
     }
-    if s1.st_size != s2.st_size {
+    let decompress = options.decompress
+        && (compress::detect(fs1, f1)?.is_some() || compress::detect(fs2, f2)?.is_some());
+    if !decompress && s1.st_size != s2.st_size {
         return Ok(false);
     }
+    if !decompress {
+        if let Some(outcome) = cmp_digests(fs1, f1, fs2, f2)? {
+            return Ok(outcome);
+        }
+    }
 
-    let key = (f1.as_ref().into(), f2.as_ref().into(), s1, s2);
-    let c_cache = Arc::clone(&CACHE);
-    let outcome = c_cache.lock().unwrap().get(&key).copied();
-    let outcome = if let Some(outcome) = outcome {
+    let cmp_content = || {
+        if decompress {
+            cmp_readers(compress::open(fs1, f1)?, compress::open(fs2, f2)?)
+        } else if local {
+            do_cmp(f1, f2, options)
+        } else {
+            cmp_readers(fs1.open(f1)?, fs2.open(f2)?)
+        }
+    };
+    if !local {
+        return cmp_content();
+    }
+
+    let key = (f1.into(), f2.into(), s1, s2, options.clone());
+    let outcome = if let Some(outcome) = cache_get(&key) {
         outcome
     } else {
-        let outcome = do_cmp(f1, f2)?;
-        if c_cache.lock().unwrap().len() > MAX_CACHE_SIZE {
-            // limit the maximum size of the cache
-            clear_cache();
-        }
-        c_cache.lock().unwrap().insert(key, outcome);
+        let outcome = cmp_content()?;
+        cache_insert(key, outcome);
         outcome
     };
 
     Ok(outcome)
 }
 
-/// Compare common files in two directories. (WIP)
+/// Compare two files by digest if either file system knows one, reading
+/// the other file if needed.
+fn cmp_digests(
+    fs1: &dyn FileSystem,
+    f1: &Path,
+    fs2: &dyn FileSystem,
+    f2: &Path,
+) -> io::Result<Option<bool>> {
+    let (d1, d2) = (fs1.digest(f1)?, fs2.digest(f2)?);
+    if d1.is_none() && d2.is_none() {
+        return Ok(None);
+    }
+    let d1 = match d1 {
+        Some(d1) => d1,
+        None => digest::sha256(fs1.open(f1)?)?,
+    };
+    let d2 = match d2 {
+        Some(d2) => d2,
+        None => digest::sha256(fs2.open(f2)?)?,
+    };
+    Ok(Some(d1 == d2))
+}
+
+/// Compare the extended attributes of two files.
+///
+/// Only the attributes matched by the patterns of `CmpOptions::xattrs()`
+/// are compared; other options are ignored.
+///
+/// # Example
+///
+/// ```rust
+/// use filecmp::{xattr_diff, CmpOptions};
+///
+/// let options = CmpOptions::new().xattrs(vec!["user.*", "security.*"]);
+/// let diff = xattr_diff("Cargo.toml", "Cargo.toml", &options).unwrap();
+/// assert!(diff.is_empty());
+/// ```
+pub fn xattr_diff(
+    f1: impl AsRef<Path>,
+    f2: impl AsRef<Path>,
+    options: &CmpOptions,
+) -> io::Result<XattrDiff> {
+    xattr_diff_in(&RealFs, f1.as_ref(), &RealFs, f2.as_ref(), &options.xattrs)
+}
+
+fn xattr_diff_in(
+    fs1: &dyn FileSystem,
+    f1: &Path,
+    fs2: &dyn FileSystem,
+    f2: &Path,
+    patterns: &[String],
+) -> io::Result<XattrDiff> {
+    Ok(XattrDiff::new(&fs1.xattrs(f1)?, &fs2.xattrs(f2)?, patterns))
+}
+
+/// Options for `cmp_with()`, `cmpfiles_with()` and `DirCmpBuilder`.
+///
+/// The default compares shallowly, like `cmp(f1, f2, true)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CmpOptions {
+    shallow: bool,
+    decompress: bool,
+    reflinks: bool,
+    digest_cache: bool,
+    xattrs: Vec<String>,
+}
+
+impl CmpOptions {
+    /// Create the default options.
+    pub fn new() -> Self {
+        CmpOptions {
+            shallow: true,
+            decompress: false,
+            reflinks: false,
+            digest_cache: false,
+            xattrs: Vec::new(),
+        }
+    }
+
+    /// Just check stat signature (do not read the files) when it matches.
+    pub fn shallow(mut self, shallow: bool) -> Self {
+        self.shallow = shallow;
+        self
+    }
+
+    /// Compare the decompressed content of compressed files.
+    ///
+    /// Compressed files are recognised by their magic bytes, not by their
+    /// extension. Supported formats depend on the enabled cargo features:
+    /// `gzip`, `xz` and `zstd`. A compressed file compares equal to another
+    /// compressed or plain file with the same payload.
+    pub fn decompress(mut self, decompress: bool) -> Self {
+        self.decompress = decompress;
+        self
+    }
+
+    /// Declare files identical without reading them when they share all
+    /// their extents, like reflinked copies on btrfs or XFS.
+    ///
+    /// Only used on Linux, for files on the real file system. Files which do
+    /// not share every extent, or file systems without FIEMAP support, are
+    /// compared by content as usual.
+    pub fn reflinks(mut self, reflinks: bool) -> Self {
+        self.reflinks = reflinks;
+        self
+    }
+
+    /// Compare files on the real file system by their SHA-256 digests,
+    /// cached in the `user.filecmp.sha256` extended attribute of each file
+    /// along with its size, mtime and inode.
+    ///
+    /// A cached digest is trusted while the file keeps its size, mtime and
+    /// inode, so unchanged files are not read again on later comparisons.
+    /// Files whose attributes cannot be read or written, such as read-only
+    /// files or file systems without user attributes, are digested every
+    /// time. Attributes are only used on Linux.
+    pub fn digest_cache(mut self, digest_cache: bool) -> Self {
+        self.digest_cache = digest_cache;
+        self
+    }
+
+    /// Also compare the extended attributes matched by `patterns`.
+    ///
+    /// A pattern is a full name such as `security.capability`, a namespace
+    /// such as `user.*` or `security.*`, or `*` for all attributes. Files
+    /// whose matched attributes differ compare unequal, even with the same
+    /// content. Without patterns (the default), attributes are ignored.
+    /// The digest cache of `digest_cache()` is never compared. Attributes
+    /// are only read on Linux.
+    pub fn xattrs<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.xattrs = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+impl Default for CmpOptions {
+    fn default() -> Self {
+        CmpOptions::new()
+    }
+}
+
+/// Decide a comparison from the stat signatures alone, if possible.
+fn cmp_signatures(s1: &Signature, s2: &Signature, shallow: bool) -> Option<bool> {
+    if s1.s_ifmt != stat::S_IFREG || s2.s_ifmt != stat::S_IFREG {
+        return Some(false);
+    }
+    if shallow && s1 == s2 {
+        return Some(true);
+    }
+    None
+}
+
+fn cache_get(key: &CacheKey) -> Option<bool> {
+    CACHE.lock().unwrap().get(key).copied()
+}
+
+fn cache_insert(key: CacheKey, outcome: bool) {
+    let c_cache = Arc::clone(&CACHE);
+    let mut cache = c_cache.lock().unwrap();
+    if cache.len() > MAX_CACHE_SIZE {
+        // limit the maximum size of the cache
+        cache.clear();
+    }
+    cache.insert(key, outcome);
+}
+
+/// Compare common files in two directories.
 ///
 /// Arguments:
 ///  - dir1 -- First directory name
@@ -129,10 +416,10 @@ pub fn cmp(f1: impl AsRef<Path>, f2: impl AsRef<Path>, shallow: bool) -> io::Res
 ///  - filepaths that are different
 ///  - filepaths that aren't regular files.
 pub fn cmpfiles<A, B, C, D>(
-    _dir1: A,
-    _dir2: B,
-    _common: D,
-    _shallow: bool,
+    dir1: A,
+    dir2: B,
+    common: D,
+    shallow: bool,
 ) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>)>
 where
     A: AsRef<Path>,
@@ -140,10 +427,55 @@ where
     C: AsRef<Path>,
     D: AsRef<[C]>,
 {
-    unimplemented!() // TODO
+    cmpfiles_with(dir1, dir2, common, &CmpOptions::new().shallow(shallow))
 }
 
-/// A struct that manages the comparison of 2 directories. (WIP)
+/// Compare common files in two directories, with options beyond the
+/// shallow flag.
+///
+/// Like `cmpfiles()`, but each pair of files is compared with `cmp_with()`.
+pub fn cmpfiles_with<A, B, C, D>(
+    dir1: A,
+    dir2: B,
+    common: D,
+    options: &CmpOptions,
+) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>)>
+where
+    A: AsRef<Path>,
+    B: AsRef<Path>,
+    C: AsRef<Path>,
+    D: AsRef<[C]>,
+{
+    let mut res = (Vec::new(), Vec::new(), Vec::new());
+    for x in common.as_ref() {
+        let ax = dir1.as_ref().join(x);
+        let bx = dir2.as_ref().join(x);
+        let x = x.as_ref().to_path_buf();
+        match cmp_with(&ax, &bx, options) {
+            Ok(true) => res.0.push(x),
+            Ok(false) => res.1.push(x),
+            Err(_) => res.2.push(x),
+        }
+    }
+    Ok(res)
+}
+
+/// Names of directory entries ignored by default.
+pub const DEFAULT_IGNORES: [&str; 8] = [
+    "RCS",
+    "CVS",
+    "tags",
+    ".git",
+    ".hg",
+    ".bzr",
+    "_darcs",
+    "__pycache__",
+];
+
+/// Names of directory entries hidden by default (os.curdir and os.pardir).
+pub const DEFAULT_HIDES: [&str; 2] = [".", ".."];
+
+/// A struct that manages the comparison of 2 directories.
 ///
 /// dircmp(a, b, ignore, hide)
 /// A and B are directories.
@@ -159,6 +491,7 @@ where
 ///    - x.report_full_closure() -> like report_partial_closure, but fully recursive.
 ///
 /// Attributes:
+///  - hide, ignore: The names left out of both listings.
 ///  - left_list, right_list: The files in dir1 and dir2, filtered by hide and ignore.
 ///  - common: a list of names in both dir1 and dir2.
 ///  - left_only, right_only: names only in dir1, dir2.
@@ -169,23 +502,761 @@ where
 ///  - diff_files: list of filenames which differ.
 ///  - funny_files: list of files which could not be compared.
 ///  - subdirs: a dictionary of dircmp objects, keyed by names in common_dirs.
-pub struct DirCmp;
+///  - same_dirs: common subdirectories with equal Merkle digests, which are
+///    not walked and have no entry in subdirs, when enabled with
+///    `DirCmpBuilder::merkle()`.
+///  - xattr_diffs: extended attribute differences of common files and
+///    subdirectories, when enabled with `DirCmpBuilder::xattrs()`.
+///  - meta_diffs: permission, mtime and owner differences of common files
+///    and subdirectories, when enabled with `DirCmpBuilder::metadata()`
+///    and `DirCmpBuilder::owners()`.
+///  - moves: files moved or renamed anywhere in the trees, when enabled
+///    with `DirCmpBuilder::renames()`; only set on the top comparison.
+///  - name_pairs: common names whose right name differs, by case or
+///    Unicode normalization, when enabled with
+///    `DirCmpBuilder::name_matching()`; see `DirCmp::right_name()`.
+///  - name_collisions: names of either directory which several names
+///    match, and which are left unpaired.
+///
+/// Unlike the Python version, all attributes are computed eagerly, so
+/// constructing a `DirCmp` walks both trees completely.
+///
+/// # Example
+///
+/// ```rust
+/// use std::env;
+/// use std::fs;
+/// use filecmp::DirCmp;
+///
+/// let temp_dir = env::temp_dir().join("filecmp_dircmp_example");
+/// let a = temp_dir.join("a");
+/// let b = temp_dir.join("b");
+/// fs::create_dir_all(&a).unwrap();
+/// fs::create_dir_all(&b).unwrap();
+/// fs::write(a.join("same.txt"), b"hello").unwrap();
+/// fs::write(b.join("same.txt"), b"hello").unwrap();
+/// fs::write(a.join("only_a.txt"), b"a").unwrap();
+///
+/// let x = DirCmp::new(&a, &b).unwrap();
+/// assert_eq!(x.same_files, vec![std::path::PathBuf::from("same.txt")]);
+/// assert_eq!(x.left_only, vec![std::path::PathBuf::from("only_a.txt")]);
+/// assert!(x.right_only.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirCmp {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    pub hide: Vec<OsString>,
+    pub ignore: Vec<OsString>,
+    pub left_list: Vec<PathBuf>,
+    pub right_list: Vec<PathBuf>,
+    pub common: Vec<PathBuf>,
+    pub left_only: Vec<PathBuf>,
+    pub right_only: Vec<PathBuf>,
+    pub common_dirs: Vec<PathBuf>,
+    pub common_files: Vec<PathBuf>,
+    pub common_funny: Vec<PathBuf>,
+    pub same_files: Vec<PathBuf>,
+    pub diff_files: Vec<PathBuf>,
+    pub funny_files: Vec<PathBuf>,
+    pub subdirs: BTreeMap<PathBuf, DirCmp>,
+    pub same_dirs: Vec<PathBuf>,
+    pub xattr_diffs: BTreeMap<PathBuf, XattrDiff>,
+    pub meta_diffs: BTreeMap<PathBuf, MetaDiff>,
+    pub moves: Vec<Move>,
+    pub name_pairs: BTreeMap<PathBuf, PathBuf>,
+    pub name_collisions: Vec<PathBuf>,
+}
+
+/// Metadata differing between two entries, see `DirCmpBuilder::metadata()`.
+///
+/// With the `serde` feature, it serializes to an object of the differing
+/// fields, each with the values of both sides.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct MetaDiff {
+    /// Permission bits of both sides, if they differ.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub mode: Option<(u32, u32)>,
+    /// Modification times of both sides, if they differ.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub mtime: Option<(f64, f64)>,
+    /// Owners of both sides, if they differ and owners are compared.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub uid: Option<(u32, u32)>,
+    /// Groups of both sides, if they differ and owners are compared.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub gid: Option<(u32, u32)>,
+}
+
+impl MetaDiff {
+    fn new(s1: &os::StatResult, s2: &os::StatResult, owners: bool) -> Self {
+        let differ = |v1, v2| {
+            if owners && v1 != v2 {
+                Some((v1, v2))
+            } else {
+                None
+            }
+        };
+        let (m1, m2) = (stat::S_IMODE(s1.st_mode), stat::S_IMODE(s2.st_mode));
+        MetaDiff {
+            mode: if m1 != m2 { Some((m1, m2)) } else { None },
+            mtime: if s1.st_mtime != s2.st_mtime {
+                Some((s1.st_mtime, s2.st_mtime))
+            } else {
+                None
+            },
+            uid: differ(s1.st_uid, s2.st_uid),
+            gid: differ(s1.st_gid, s2.st_gid),
+        }
+    }
+
+    /// Whether nothing differs.
+    pub fn is_empty(&self) -> bool {
+        self.mode.is_none() && self.mtime.is_none() && self.uid.is_none() && self.gid.is_none()
+    }
+}
 
 impl DirCmp {
-    // TODO
-    pub fn new(_a: impl AsRef<Path>, _b: impl AsRef<Path>) -> Self {
-        unimplemented!() // TODO
+    /// Compare directories `a` and `b` with the default ignore and hide
+    /// lists and shallow file comparison.
+    pub fn new(a: impl AsRef<Path>, b: impl AsRef<Path>) -> io::Result<Self> {
+        DirCmpBuilder::new(a, b).build()
     }
 
-    pub fn report_full_closure(&self) {
-        unimplemented!() // TODO
+    /// Compute the name lists of a comparison (phase 1 in filecmp.py).
+    fn from_lists(
+        a: &Path,
+        b: &Path,
+        (hide, ignore): (&[OsString], &[OsString]),
+        left_list: Vec<PathBuf>,
+        right_list: Vec<PathBuf>,
+    ) -> Self {
+        let left_set: HashSet<&PathBuf> = left_list.iter().collect();
+        let right_set: HashSet<&PathBuf> = right_list.iter().collect();
+        let common = left_list
+            .iter()
+            .filter(|x| right_set.contains(x))
+            .cloned()
+            .collect();
+        let left_only = left_list
+            .iter()
+            .filter(|x| !right_set.contains(x))
+            .cloned()
+            .collect();
+        let right_only = right_list
+            .iter()
+            .filter(|x| !left_set.contains(x))
+            .cloned()
+            .collect();
+        DirCmp {
+            left: a.to_path_buf(),
+            right: b.to_path_buf(),
+            hide: hide.to_vec(),
+            ignore: ignore.to_vec(),
+            left_list,
+            right_list,
+            common,
+            left_only,
+            right_only,
+            common_dirs: Vec::new(),
+            common_files: Vec::new(),
+            common_funny: Vec::new(),
+            same_files: Vec::new(),
+            diff_files: Vec::new(),
+            funny_files: Vec::new(),
+            subdirs: BTreeMap::new(),
+            same_dirs: Vec::new(),
+            xattr_diffs: BTreeMap::new(),
+            meta_diffs: BTreeMap::new(),
+            moves: Vec::new(),
+            name_pairs: BTreeMap::new(),
+            name_collisions: Vec::new(),
+        }
     }
 
+    /// Sort a common name into dirs, files or funny cases by the stat
+    /// results of both sides (phase 2 in filecmp.py).
+    fn classify(
+        &mut self,
+        x: &Path,
+        a_stat: io::Result<os::StatResult>,
+        b_stat: io::Result<os::StatResult>,
+    ) {
+        let (a_stat, b_stat) = match (a_stat, b_stat) {
+            (Ok(a_stat), Ok(b_stat)) => (a_stat, b_stat),
+            _ => {
+                self.common_funny.push(x.to_path_buf());
+                return;
+            }
+        };
+        let a_type = stat::S_IFMT(a_stat.st_mode);
+        let b_type = stat::S_IFMT(b_stat.st_mode);
+        if a_type != b_type {
+            self.common_funny.push(x.to_path_buf());
+        } else if stat::S_ISDIR(a_type) {
+            self.common_dirs.push(x.to_path_buf());
+        } else if stat::S_ISREG(a_type) || stat::S_ISLNK(a_type) {
+            self.common_files.push(x.to_path_buf());
+        } else {
+            self.funny_files.push(x.to_path_buf());
+        }
+    }
+
+    /// Print a report on the differences between a and b.
     pub fn report(&self) {
-        unimplemented!() // TODO
+        print!("{}", ReportDisplay(self));
+    }
+
+    /// Print reports on self and on subdirs.
+    pub fn report_partial_closure(&self) {
+        self.report();
+        for sd in self.subdirs.values() {
+            println!();
+            sd.report();
+        }
+    }
+
+    /// Report on self and subdirs recursively.
+    pub fn report_full_closure(&self) {
+        self.report();
+        for sd in self.subdirs.values() {
+            println!();
+            sd.report_full_closure();
+        }
+    }
+}
+
+/// Output format of `DirCmp::report()`, kept close to filecmp.py.
+struct ReportDisplay<'a>(&'a DirCmp);
+
+impl fmt::Display for ReportDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dd = self.0;
+        writeln!(f, "diff {} {}", dd.left.display(), dd.right.display())?;
+        if !dd.left_only.is_empty() {
+            writeln!(
+                f,
+                "Only in {} : {}",
+                dd.left.display(),
+                PyList(&dd.left_only)
+            )?;
+        }
+        if !dd.right_only.is_empty() {
+            writeln!(
+                f,
+                "Only in {} : {}",
+                dd.right.display(),
+                PyList(&dd.right_only)
+            )?;
+        }
+        if !dd.same_files.is_empty() {
+            writeln!(f, "Identical files : {}", PyList(&dd.same_files))?;
+        }
+        if !dd.diff_files.is_empty() {
+            writeln!(f, "Differing files : {}", PyList(&dd.diff_files))?;
+        }
+        if !dd.funny_files.is_empty() {
+            writeln!(f, "Trouble with common files : {}", PyList(&dd.funny_files))?;
+        }
+        if !dd.common_dirs.is_empty() {
+            writeln!(f, "Common subdirectories : {}", PyList(&dd.common_dirs))?;
+        }
+        if !dd.same_dirs.is_empty() {
+            writeln!(f, "Identical subdirectories : {}", PyList(&dd.same_dirs))?;
+        }
+        if !dd.common_funny.is_empty() {
+            writeln!(f, "Common funny cases : {}", PyList(&dd.common_funny))?;
+        }
+        if !dd.name_pairs.is_empty() {
+            let pairs: Vec<String> = dd
+                .name_pairs
+                .iter()
+                .map(|(x, y)| format!("'{}' = '{}'", x.display(), y.display()))
+                .collect();
+            writeln!(f, "Paired names : [{}]", pairs.join(", "))?;
+        }
+        if !dd.name_collisions.is_empty() {
+            writeln!(f, "Colliding names : {}", PyList(&dd.name_collisions))?;
+        }
+        for m in &dd.moves {
+            writeln!(f, "Moved : '{}' -> '{}'", m.from.display(), m.to.display())?;
+        }
+        if !dd.xattr_diffs.is_empty() {
+            let names: Vec<PathBuf> = dd.xattr_diffs.keys().cloned().collect();
+            writeln!(f, "Differing xattrs : {}", PyList(&names))?;
+        }
+        if !dd.meta_diffs.is_empty() {
+            let names: Vec<PathBuf> = dd.meta_diffs.keys().cloned().collect();
+            writeln!(f, "Differing metadata : {}", PyList(&names))?;
+        }
+        Ok(())
+    }
+}
+
+/// Display a list of names like the repr of a Python list.
+struct PyList<'a>(&'a [PathBuf]);
+
+impl fmt::Display for PyList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, x) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "'{}'", x.display())?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Builder for a `DirCmp` with non-default options.
+///
+/// # Example
+///
+/// ```rust
+/// use filecmp::DirCmpBuilder;
+///
+/// let builder = DirCmpBuilder::new("dir1", "dir2")
+///     .ignore(vec!["target", ".git"])
+///     .shallow(false);
+/// // let x = builder.build()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirCmpBuilder {
+    left: PathBuf,
+    right: PathBuf,
+    ignore: Vec<OsString>,
+    hide: Vec<OsString>,
+    options: CmpOptions,
+    archives: bool,
+    left_fs: Option<Arc<dyn FileSystem>>,
+    right_fs: Option<Arc<dyn FileSystem>>,
+    /// Similarity threshold of rename detection, if enabled.
+    renames: Option<f64>,
+    follow_symlinks: bool,
+    metadata: bool,
+    owners: bool,
+    merkle: bool,
+    name_matching: NameMatching,
+}
+
+impl DirCmpBuilder {
+    /// Create a builder comparing directories `a` and `b`.
+    pub fn new(a: impl AsRef<Path>, b: impl AsRef<Path>) -> Self {
+        DirCmpBuilder {
+            left: a.as_ref().to_path_buf(),
+            right: b.as_ref().to_path_buf(),
+            ignore: DEFAULT_IGNORES.iter().map(OsString::from).collect(),
+            hide: DEFAULT_HIDES.iter().map(OsString::from).collect(),
+            options: CmpOptions::new(),
+            archives: false,
+            left_fs: None,
+            right_fs: None,
+            renames: None,
+            follow_symlinks: FOLLOW_SYMLINKS_DEFAULT,
+            metadata: false,
+            owners: false,
+            merkle: false,
+            name_matching: NameMatching::Exact,
+        }
+    }
+
+    /// Names to ignore, replacing DEFAULT_IGNORES.
+    pub fn ignore<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.ignore = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Names to hide, replacing DEFAULT_HIDES.
+    pub fn hide<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.hide = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Compare files by stat() signature only (the default), or by content.
+    pub fn shallow(mut self, shallow: bool) -> Self {
+        self.options = self.options.shallow(shallow);
+        self
+    }
+
+    /// Compare the decompressed content of compressed files, see
+    /// `CmpOptions::decompress()`.
+    pub fn decompress(mut self, decompress: bool) -> Self {
+        self.options = self.options.decompress(decompress);
+        self
+    }
+
+    /// Treat files sharing all their extents as identical, see
+    /// `CmpOptions::reflinks()`.
+    pub fn reflinks(mut self, reflinks: bool) -> Self {
+        self.options = self.options.reflinks(reflinks);
+        self
+    }
+
+    /// Compare files by digests cached in extended attributes, see
+    /// `CmpOptions::digest_cache()`.
+    pub fn digest_cache(mut self, digest_cache: bool) -> Self {
+        self.options = self.options.digest_cache(digest_cache);
+        self
+    }
+
+    /// Compare the extended attributes matched by `patterns`, see
+    /// `CmpOptions::xattrs()`. Differences are listed in
+    /// `DirCmp::xattr_diffs`.
+    pub fn xattrs<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options = self.options.xattrs(patterns);
+        self
+    }
+
+    /// Compare the permission bits and mtimes of common files and
+    /// subdirectories. Differences are listed in `DirCmp::meta_diffs`,
+    /// whether or not the files are the same.
+    pub fn metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Also compare the owners and groups of common files and
+    /// subdirectories, with `metadata()`. Off by default, as file systems
+    /// without owners, such as manifests, report them as 0.
+    pub fn owners(mut self, owners: bool) -> Self {
+        self.owners = owners;
+        self
+    }
+
+    /// Replace all file comparison options at once.
+    pub fn cmp_options(mut self, options: CmpOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Detect files moved or renamed between the trees, by identical
+    /// content: files of the same size are matched by their SHA-256
+    /// digests, from the file system or the digest cache when available.
+    ///
+    /// Matching files are listed in `DirCmp::moves` with their paths in
+    /// both trees, and removed from `left_only` and `right_only`. Files
+    /// inside directories only found in one tree are matched too, but
+    /// those directories stay listed. Empty files are never matched.
+    pub fn renames(mut self, renames: bool) -> Self {
+        self.renames = if renames { Some(1.0) } else { None };
+        self
+    }
+
+    /// Detect moves like `renames(true)`, and also match files whose lines
+    /// are similar enough: `threshold` is the share of lines two files
+    /// must have in common, above 0.0 and up to 1.0. Files are paired
+    /// greedily, most similar first; those over 8 MiB are only matched
+    /// when identical, as are all files when either tree has over 1000
+    /// unmatched ones. Files whose sizes differ by more than `threshold`
+    /// allows are not compared. `build()` fails with `InvalidInput` when
+    /// `threshold` is outside that range or NaN.
+    pub fn rename_similarity(mut self, threshold: f64) -> Self {
+        self.renames = Some(threshold);
+        self
+    }
+
+    /// Treat `a` or `b` as a directory tree when it is a tar or zip archive
+    /// file instead of a directory.
+    ///
+    /// Members are compared by path, and reported below the archive path.
+    /// Archives are read whole into memory when the comparison starts, and
+    /// tar hard links must follow their target. Needs the `tar` or `zip`
+    /// feature.
+    #[cfg(any(feature = "tar", feature = "zip"))]
+    pub fn archives(mut self, archives: bool) -> Self {
+        self.archives = archives;
+        self
+    }
+
+    /// Follow symbolic links (the default), comparing what they point to,
+    /// or compare the links themselves by their targets.
+    ///
+    /// Without following, a link is a file of its own type: it is the same
+    /// as a link with the same target, and funny against anything else.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Skip the common subdirectories whose Merkle digests are equal; they
+    /// are listed in `DirCmp::same_dirs`.
+    ///
+    /// Each pair of common subdirectories is compared by digest when the
+    /// walk reaches it, stopping at the first entry that differs. File
+    /// digests read whole files, so this pays off when the file systems
+    /// know them already, as with manifests, or with
+    /// `CmpOptions::digest_cache()`, which caches them between runs.
+    /// Metadata and extended attributes are not part of the digests, so
+    /// nothing is skipped with `metadata()` or `CmpOptions::xattrs()`. See
+    /// the `merkle` module.
+    pub fn merkle(mut self, merkle: bool) -> Self {
+        self.merkle = merkle;
+        self
+    }
+
+    /// Pair names which differ only by case or Unicode normalization, as
+    /// found in trees copied from macOS or FAT and exFAT volumes.
+    ///
+    /// Paired entries are compared like common ones, under their left
+    /// name, and listed in `DirCmp::name_pairs`. Names matching another
+    /// name of the same directory are listed in `DirCmp::name_collisions`
+    /// and only paired exactly.
+    pub fn name_matching(mut self, name_matching: NameMatching) -> Self {
+        self.name_matching = name_matching;
+        self
+    }
+
+    /// Read `a` from the given file system instead of the real one.
+    pub fn left_fs(mut self, fs: impl FileSystem + 'static) -> Self {
+        self.left_fs = Some(Arc::new(fs));
+        self
+    }
+
+    /// Read `b` from the given file system instead of the real one.
+    pub fn right_fs(mut self, fs: impl FileSystem + 'static) -> Self {
+        self.right_fs = Some(Arc::new(fs));
+        self
+    }
+
+    /// Walk both directories and build the comparison.
+    pub fn build(&self) -> io::Result<DirCmp> {
+        if let Some(threshold) = self.renames {
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "rename similarity {} is not above 0.0 and up to 1.0",
+                        threshold
+                    ),
+                ));
+            }
+        }
+        let left_src = Source::open(&self.left_fs, &self.left, self.archives)?;
+        let right_src = Source::open(&self.right_fs, &self.right, self.archives)?;
+        let digests = if self.merkle && !self.metadata && self.options.xattrs.is_empty() {
+            Some(merkle::Digests::new(self, &left_src, &right_src))
+        } else {
+            None
+        };
+        let mut dd = self.build_at(
+            &left_src,
+            &self.left,
+            &right_src,
+            &self.right,
+            digests.as_ref(),
+        )?;
+        if let Some(threshold) = self.renames {
+            self.detect_moves(&mut dd, &left_src, &right_src, threshold)?;
+        }
+        Ok(dd)
+    }
+
+    fn build_at(
+        &self,
+        left_src: &Source,
+        a: &Path,
+        right_src: &Source,
+        b: &Path,
+        digests: Option<&merkle::Digests>,
+    ) -> io::Result<DirCmp> {
+        let mut dd = self.list_level(left_src, a, right_src, b)?;
+
+        for x in &dd.common_files {
+            let y = dd.right_name(x);
+            match self.cmp_entry(left_src, &a.join(x), right_src, &b.join(y)) {
+                Ok(true) => dd.same_files.push(x.clone()),
+                Ok(false) => dd.diff_files.push(x.clone()),
+                Err(_) => dd.funny_files.push(x.clone()),
+            }
+        }
+
+        if !self.options.xattrs.is_empty() {
+            for x in dd.common_files.iter().chain(&dd.common_dirs) {
+                let (fs1, fs2) = (left_src.fs(), right_src.fs());
+                if let Ok(diff) = xattr_diff_in(
+                    fs1,
+                    &a.join(x),
+                    fs2,
+                    &b.join(dd.right_name(x)),
+                    &self.options.xattrs,
+                ) {
+                    if !diff.is_empty() {
+                        dd.xattr_diffs.insert(x.clone(), diff);
+                    }
+                }
+            }
+        }
+
+        if self.metadata {
+            for x in dd.common_files.iter().chain(&dd.common_dirs) {
+                let s1 = left_src.fs().stat(&a.join(x), self.follow_symlinks);
+                let s2 = right_src
+                    .fs()
+                    .stat(&b.join(dd.right_name(x)), self.follow_symlinks);
+                if let (Ok(s1), Ok(s2)) = (s1, s2) {
+                    let diff = MetaDiff::new(&s1, &s2, self.owners);
+                    if !diff.is_empty() {
+                        dd.meta_diffs.insert(x.clone(), diff);
+                    }
+                }
+            }
+        }
+
+        for x in &dd.common_dirs {
+            let (a, b) = (a.join(x), b.join(dd.right_name(x)));
+            if let Some(digests) = digests {
+                if let (Ok(a_rel), Ok(b_rel)) =
+                    (a.strip_prefix(&self.left), b.strip_prefix(&self.right))
+                {
+                    if digests.same(a_rel, b_rel) {
+                        dd.same_dirs.push(x.clone());
+                        continue;
+                    }
+                }
+            }
+            let sd = self.build_at(left_src, &a, right_src, &b, digests)?;
+            dd.subdirs.insert(x.clone(), sd);
+        }
+
+        Ok(dd)
+    }
+
+    /// List and classify the entries of `a` and `b`, without comparing
+    /// files or walking subdirectories.
+    fn list_level(
+        &self,
+        left_src: &Source,
+        a: &Path,
+        right_src: &Source,
+        b: &Path,
+    ) -> io::Result<DirCmp> {
+        let left_list = self.filter(left_src.fs().read_dir(a)?);
+        let right_list = self.filter(right_src.fs().read_dir(b)?);
+        let mut dd = DirCmp::from_lists(a, b, self.filter_lists(), left_list, right_list);
+        dd.match_names(self.name_matching);
+
+        for x in dd.common.clone() {
+            let a_stat = left_src.fs().stat(&a.join(&x), self.follow_symlinks);
+            let b_stat = right_src
+                .fs()
+                .stat(&b.join(dd.right_name(&x)), self.follow_symlinks);
+            dd.classify(&x, a_stat, b_stat);
+        }
+        Ok(dd)
+    }
+
+    /// Compare a common file, or the targets of a common symbolic link
+    /// when links are not followed.
+    fn cmp_entry(
+        &self,
+        left_src: &Source,
+        f1: &Path,
+        right_src: &Source,
+        f2: &Path,
+    ) -> io::Result<bool> {
+        if !self.follow_symlinks && stat::S_ISLNK(left_src.fs().stat(f1, false)?.st_mode) {
+            return Ok(left_src.fs().read_link(f1)? == right_src.fs().read_link(f2)?);
+        }
+        cmp_sources(left_src, f1, right_src, f2, &self.options)
+    }
+
+    /// Remove hidden and ignored names and sort the rest.
+    fn filter(&self, names: Vec<PathBuf>) -> Vec<PathBuf> {
+        filter_names(names, &self.hide, &self.ignore)
+    }
+
+    /// The hide and ignore lists.
+    fn filter_lists(&self) -> (&[OsString], &[OsString]) {
+        (&self.hide, &self.ignore)
+    }
+}
+
+/// Remove hidden and ignored names and sort the rest.
+fn filter_names(names: Vec<PathBuf>, hide: &[OsString], ignore: &[OsString]) -> Vec<PathBuf> {
+    let mut names: Vec<PathBuf> = names
+        .into_iter()
+        .filter(|x| {
+            let x = x.as_os_str();
+            !hide.iter().any(|h| h == x) && !ignore.iter().any(|i| i == x)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// Where the entries of one side of a `DirCmp` come from.
+enum Source {
+    Real,
+    Virtual(Arc<dyn FileSystem>),
+}
+
+impl Source {
+    /// Pick the source for a tree root, loading it if it is an archive.
+    #[allow(unused_variables)]
+    fn open(fs: &Option<Arc<dyn FileSystem>>, root: &Path, archives: bool) -> io::Result<Source> {
+        if let Some(fs) = fs {
+            return Ok(Source::Virtual(Arc::clone(fs)));
+        }
+        #[cfg(any(feature = "tar", feature = "zip"))]
+        {
+            if archives && root.is_file() && archive::detect(root)?.is_some() {
+                return Ok(Source::Virtual(Arc::new(archive::open(root)?)));
+            }
+        }
+        Ok(Source::Real)
+    }
+
+    fn fs(&self) -> &dyn FileSystem {
+        match self {
+            Source::Real => &RealFs,
+            Source::Virtual(fs) => fs.as_ref(),
+        }
+    }
+
+    /// The SHA-256 digest of a regular file, from the file system when it
+    /// knows it, else from the cache of `CmpOptions::digest_cache()` for
+    /// real files, else by reading it.
+    fn sha256(&self, path: &Path, digest_cache: bool) -> io::Result<String> {
+        match self.fs().digest(path)? {
+            Some(digest) => Ok(digest),
+            None if digest_cache && matches!(self, Source::Real) => digest::cached_sha256(path),
+            None => digest::sha256(self.fs().open(path)?),
+        }
     }
 }
 
+/// Compare two files which may come from other file systems.
+///
+/// Files on the real file system on both sides go through the cache.
+fn cmp_sources(
+    s1: &Source,
+    f1: &Path,
+    s2: &Source,
+    f2: &Path,
+    options: &CmpOptions,
+) -> io::Result<bool> {
+    let local = matches!((s1, s2), (Source::Real, Source::Real));
+    cmp_in(s1.fs(), f1, s2.fs(), f2, options, local)
+}
+
 fn sig(st: os::StatResult) -> Signature {
     Signature {
         s_ifmt: stat::S_IFMT(st.st_mode),
@@ -194,14 +1265,36 @@ fn sig(st: os::StatResult) -> Signature {
     }
 }
 
-fn do_cmp(f1: impl AsRef<Path>, f2: impl AsRef<Path>) -> io::Result<bool> {
-    let mut f1 = File::open(f1.as_ref())?;
-    let mut f2 = File::open(f2.as_ref())?;
+/// Compare the content of two files on the real file system.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn do_cmp(f1: &Path, f2: &Path, options: &CmpOptions) -> io::Result<bool> {
+    let file1 = File::open(f1)?;
+    let file2 = File::open(f2)?;
+    #[cfg(target_os = "linux")]
+    {
+        if options.reflinks && fiemap::same_extents(&file1, &file2) {
+            return Ok(true);
+        }
+    }
+    if options.digest_cache {
+        return Ok(digest::cached_sha256(f1)? == digest::cached_sha256(f2)?);
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(outcome) = sparse::cmp(&file1, &file2)? {
+            return Ok(outcome);
+        }
+    }
+    cmp_readers(file1, file2)
+}
+
+/// Compare two byte streams to the end.
+fn cmp_readers(mut r1: impl Read, mut r2: impl Read) -> io::Result<bool> {
+    let mut buf1: [u8; BUFSIZE] = [0; BUFSIZE];
+    let mut buf2: [u8; BUFSIZE] = [0; BUFSIZE];
     loop {
-        let mut buf1: [u8; BUFSIZE] = [0; BUFSIZE];
-        let mut buf2: [u8; BUFSIZE] = [0; BUFSIZE];
-        let len1 = f1.read(&mut buf1)?;
-        let len2 = f2.read(&mut buf2)?;
+        let len1 = read_full(&mut r1, &mut buf1)?;
+        let len2 = read_full(&mut r2, &mut buf2)?;
         if len1 != len2 {
             return Ok(false);
         }
@@ -209,12 +1302,27 @@ fn do_cmp(f1: impl AsRef<Path>, f2: impl AsRef<Path>) -> io::Result<bool> {
         if read_size == 0 {
             return Ok(true);
         }
-        if &buf1[..read_size] != &buf2[..read_size] {
+        if buf1[..read_size] != buf2[..read_size] {
             return Ok(false);
         }
     }
 }
 
+/// Read until `buf` is full or the end of stream is reached, since readers
+/// like decoders may hand back shorter chunks than requested.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match r.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
 #[derive(Debug)]
 struct Signature {
     s_ifmt: u32,
@@ -253,6 +1361,7 @@ mod tests {
     use std::io::Write;
 
     #[test]
+    #[allow(clippy::unused_io_amount)]
     fn test_stat() {
         let temp_dir = env::temp_dir();
         let test_dir = dbg!(temp_dir.join("test_filecmp").join("test_stat"));
@@ -301,4 +1410,46 @@ mod tests {
         assert!(!cmp(&foo_path, &baz_path, shallow).unwrap());
         assert!(!cmp(&bar_path, &baz_path, shallow).unwrap());
     }
+
+    #[test]
+    fn test_dircmp() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_dircmp");
+        let a = test_dir.join("a");
+        let b = test_dir.join("b");
+        for d in &[a.join("sub"), b.join("sub"), a.join("funny")] {
+            fs::create_dir_all(d).unwrap();
+        }
+        fs::write(a.join("same.txt"), b"same").unwrap();
+        fs::write(b.join("same.txt"), b"same").unwrap();
+        fs::write(a.join("sub").join("diff.txt"), b"left").unwrap();
+        fs::write(b.join("sub").join("diff.txt"), b"right").unwrap();
+        fs::write(b.join("funny"), b"not a directory").unwrap();
+        fs::write(a.join("only_a.txt"), b"a").unwrap();
+        fs::create_dir_all(a.join(".git")).unwrap();
+
+        let x = DirCmpBuilder::new(&a, &b).shallow(false).build().unwrap();
+        assert_eq!(
+            x.left_list,
+            vec![
+                PathBuf::from("funny"),
+                "only_a.txt".into(),
+                "same.txt".into(),
+                "sub".into()
+            ]
+        );
+        assert_eq!(x.left_only, vec![PathBuf::from("only_a.txt")]);
+        assert!(x.right_only.is_empty());
+        assert_eq!(x.common_funny, vec![PathBuf::from("funny")]);
+        assert_eq!(x.common_dirs, vec![PathBuf::from("sub")]);
+        assert_eq!(x.same_files, vec![PathBuf::from("same.txt")]);
+        assert_eq!(
+            x.subdirs[Path::new("sub")].diff_files,
+            vec![PathBuf::from("diff.txt")]
+        );
+
+        let (same, diff, funny) = cmpfiles(&a, &b, ["same.txt", "missing.txt"], false).unwrap();
+        assert_eq!(same, vec![PathBuf::from("same.txt")]);
+        assert!(diff.is_empty());
+        assert_eq!(funny, vec![PathBuf::from("missing.txt")]);
+    }
 }
@@ -0,0 +1,585 @@
+//! Manifests: snapshots of a tree's metadata and content digests.
+//!
+//! A manifest lists every entry below a root with its type, permission
+//! bits, size, mtime and, for regular files, the SHA-256 digest of its
+//! content. It is enough to tell later whether the tree changed, without
+//! keeping a copy of it.
+//!
+//! The text format starts with the line `filecmp-manifest 1`, followed by
+//! one line per entry, sorted by path, with tab-separated fields:
+//!
+//! ```text
+//! <type> <mode> <size> <mtime> <sha256> <path> [<target>]
+//! ```
+//!
+//!  - type: `f` file, `d` directory, `l` symbolic link, `p` fifo,
+//!    `c` character device, `b` block device, `s` socket.
+//!  - mode: permission bits in octal, such as `0644`.
+//!  - size: in bytes, `0` for directories.
+//!  - mtime: seconds since the epoch, with a fraction if any.
+//!  - sha256: lowercase hex digest of regular files, `-` for others.
+//!  - path: relative to the root, with `\\`, tab, newline, carriage
+//!    return and bytes which are not UTF-8 escaped as `\\`, `\t`, `\n`,
+//!    `\r` and `\xNN`.
+//!  - target: the target of symbolic links, escaped like path.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::env;
+//! use std::fs;
+//! use std::path::Path;
+//! use filecmp::manifest::Manifest;
+//!
+//! let temp_dir = env::temp_dir().join("filecmp_manifest_example");
+//! fs::create_dir_all(temp_dir.join("docs")).unwrap();
+//! fs::write(temp_dir.join("docs").join("readme.txt"), b"hello").unwrap();
+//!
+//! let manifest = Manifest::new(&temp_dir).unwrap();
+//! let entry = &manifest.entries[Path::new("docs/readme.txt")];
+//! assert_eq!(entry.size, 5);
+//! assert_eq!(
+//!     entry.digest.as_deref(),
+//!     Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+//! );
+//!
+//! let text = manifest.to_string();
+//! assert_eq!(text.parse::<Manifest>().unwrap(), manifest);
+//! ```
+//!
+//! A directory is compared against a manifest through `ManifestFs`, which
+//! serves the manifest as the right side of a `DirCmp`. Files whose
+//! signatures differ are compared by digest, and `DirCmpBuilder::metadata()`
+//! lists permission and mtime mismatches. `compare()` does all of this.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::vfs::{self, FileSystem, RealFs, StatResult};
+use crate::{
+    digest, filter_names, os, stat, DirCmp, DirCmpBuilder, DEFAULT_HIDES, DEFAULT_IGNORES,
+};
+
+/// First line of the manifest text format.
+const HEADER: &str = "filecmp-manifest";
+
+/// A snapshot of a tree, keyed by paths relative to its root.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub entries: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+/// The recorded state of one entry of a `Manifest`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    /// File type and permission bits, as st_mode.
+    pub mode: u32,
+    /// Size in bytes, 0 for directories.
+    pub size: u64,
+    pub mtime: f64,
+    /// Lowercase hex SHA-256 digest, for regular files only.
+    pub digest: Option<String>,
+    /// Target of a symbolic link.
+    pub target: Option<PathBuf>,
+}
+
+impl Manifest {
+    /// Version of the text format written by `Display`.
+    pub const VERSION: u32 = 1;
+
+    /// Snapshot the directory `root` with the default ignore and hide
+    /// lists.
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        ManifestBuilder::new(root).build()
+    }
+}
+
+/// Writes the text format described in the module documentation.
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", HEADER, Manifest::VERSION)?;
+        for (path, entry) in &self.entries {
+            let digest = entry.digest.as_deref().unwrap_or("-");
+            write!(
+                f,
+                "{}\t{:04o}\t{}\t{}\t{}\t{}",
+                type_char(entry.mode),
+                stat::S_IMODE(entry.mode),
+                entry.size,
+                entry.mtime,
+                digest,
+                escape(path)
+            )?;
+            if let Some(target) = &entry.target {
+                write!(f, "\t{}", escape(target))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the text format, failing with `InvalidData` on unknown versions
+/// and malformed lines.
+impl FromStr for Manifest {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let mut lines = s.lines();
+        let header = lines.next().unwrap_or("");
+        match header.strip_prefix(HEADER).map(str::trim) {
+            Some(version) if version == Manifest::VERSION.to_string() => {}
+            Some(version) => {
+                return Err(invalid(format!(
+                    "unsupported manifest version '{}'",
+                    version
+                )))
+            }
+            None => return Err(invalid("not a filecmp manifest".to_string())),
+        }
+        let mut manifest = Manifest::default();
+        for (i, line) in lines.enumerate() {
+            let (path, entry) =
+                parse_line(line).map_err(|err| invalid(format!("line {}: {}", i + 2, err)))?;
+            manifest.entries.insert(path, entry);
+        }
+        Ok(manifest)
+    }
+}
+
+/// Builder of a `Manifest`, with the ignore and hide lists of
+/// `DirCmpBuilder`.
+#[derive(Debug, Clone)]
+pub struct ManifestBuilder {
+    root: PathBuf,
+    ignore: Vec<OsString>,
+    hide: Vec<OsString>,
+    fs: Option<Arc<dyn FileSystem>>,
+}
+
+impl ManifestBuilder {
+    /// Create a builder snapshotting the directory `root`.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        ManifestBuilder {
+            root: root.as_ref().to_path_buf(),
+            ignore: DEFAULT_IGNORES.iter().map(OsString::from).collect(),
+            hide: DEFAULT_HIDES.iter().map(OsString::from).collect(),
+            fs: None,
+        }
+    }
+
+    /// Names to ignore, replacing DEFAULT_IGNORES.
+    pub fn ignore<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.ignore = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Names to hide, replacing DEFAULT_HIDES.
+    pub fn hide<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.hide = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Read the tree from the given file system instead of the real one.
+    pub fn fs(mut self, fs: impl FileSystem + 'static) -> Self {
+        self.fs = Some(Arc::new(fs));
+        self
+    }
+
+    /// Walk the tree and digest its files. Symbolic links are recorded,
+    /// not followed.
+    pub fn build(&self) -> io::Result<Manifest> {
+        let fs: &dyn FileSystem = match &self.fs {
+            Some(fs) => fs.as_ref(),
+            None => &RealFs,
+        };
+        let mut manifest = Manifest::default();
+        self.build_at(fs, Path::new(""), &mut manifest)?;
+        Ok(manifest)
+    }
+
+    fn build_at(&self, fs: &dyn FileSystem, rel: &Path, manifest: &mut Manifest) -> io::Result<()> {
+        let dir = self.root.join(rel);
+        for name in filter_names(fs.read_dir(&dir)?, &self.hide, &self.ignore) {
+            let path = dir.join(&name);
+            let st = fs.stat(&path, false)?;
+            let mut entry = ManifestEntry {
+                mode: st.st_mode,
+                size: st.st_size,
+                mtime: st.st_mtime,
+                digest: None,
+                target: None,
+            };
+            if stat::S_ISREG(st.st_mode) {
+                entry.digest = Some(digest::sha256(fs.open(&path)?)?);
+            } else if stat::S_ISLNK(st.st_mode) {
+                entry.target = Some(fs.read_link(&path)?);
+            } else if stat::S_ISDIR(st.st_mode) {
+                entry.size = 0;
+                self.build_at(fs, &rel.join(&name), manifest)?;
+            }
+            manifest.entries.insert(rel.join(name), entry);
+        }
+        Ok(())
+    }
+}
+
+/// Compare the directory `dir` against the manifest file `manifest`, with
+/// the default ignore and hide lists, shallow file comparison and
+/// metadata comparison.
+///
+/// Entries missing from the directory are in `right_only`, new entries in
+/// `left_only`, files whose digest does not match in `diff_files` and
+/// permission or mtime mismatches in `meta_diffs`.
+pub fn compare(dir: impl AsRef<Path>, manifest: impl AsRef<Path>) -> io::Result<DirCmp> {
+    let manifest = manifest.as_ref();
+    let text = std::fs::read_to_string(manifest)?;
+    DirCmpBuilder::new(dir, manifest)
+        .right_fs(ManifestFs::new(manifest, text.parse()?))
+        .metadata(true)
+        .build()
+}
+
+/// A `Manifest` served as a file system rooted at `root`.
+///
+/// Entries have the recorded metadata and digests, but no content:
+/// `open()` fails, and comparisons use `digest()` instead.
+#[derive(Debug, Clone)]
+pub struct ManifestFs {
+    root: PathBuf,
+    manifest: Manifest,
+}
+
+impl ManifestFs {
+    /// Serve `manifest` with its entries below `root`.
+    pub fn new(root: impl AsRef<Path>, manifest: Manifest) -> Self {
+        ManifestFs {
+            root: root.as_ref().to_path_buf(),
+            manifest,
+        }
+    }
+
+    fn resolve(&self, path: &Path, follow_symlinks: bool) -> io::Result<PathBuf> {
+        let entries = &self.manifest.entries;
+        vfs::resolve(&self.root, path, follow_symlinks, |rel| {
+            entries.get(rel).map(|entry| entry.target.as_deref())
+        })
+    }
+
+    /// Find the entry at `path`; `None` for the root directory.
+    fn entry(&self, path: &Path, follow_symlinks: bool) -> io::Result<Option<&ManifestEntry>> {
+        let rel = self.resolve(path, follow_symlinks)?;
+        Ok(self.manifest.entries.get(&rel))
+    }
+}
+
+impl FileSystem for ManifestFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let dir = self.resolve(path, true)?;
+        let entries = &self.manifest.entries;
+        if let Some(entry) = entries.get(&dir) {
+            if !stat::S_ISDIR(entry.mode) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}: not a directory", path.display()),
+                ));
+            }
+        }
+        Ok(entries
+            .range(dir.clone()..)
+            .take_while(|(name, _)| name.starts_with(&dir))
+            .filter(|(name, _)| name.parent() == Some(&dir))
+            .filter_map(|(name, _)| name.file_name().map(PathBuf::from))
+            .collect())
+    }
+
+    fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<StatResult> {
+        let (mode, size, mtime) = match self.entry(path, follow_symlinks)? {
+            Some(entry) => (entry.mode, entry.size, entry.mtime),
+            None => (stat::S_IFDIR | 0o755, 0, 0.0),
+        };
+        Ok(StatResult {
+            st_mode: mode,
+            st_ino: 0,
+            st_dev: 0,
+            st_nlink: 1,
+            st_uid: 0,
+            st_gid: 0,
+            st_size: size,
+            st_atime: mtime,
+            st_mtime: mtime,
+            st_ctime: mtime,
+        })
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{}: manifests have no file content", path.display()),
+        ))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self
+            .entry(path, false)?
+            .and_then(|entry| entry.target.clone())
+        {
+            Some(target) => Ok(target),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: not a symbolic link", path.display()),
+            )),
+        }
+    }
+
+    fn digest(&self, path: &Path) -> io::Result<Option<String>> {
+        Ok(self
+            .entry(path, true)?
+            .and_then(|entry| entry.digest.clone()))
+    }
+}
+
+/// The type letter of a mode, as in `find -printf %y`.
+fn type_char(mode: u32) -> char {
+    match stat::S_IFMT(mode) {
+        stat::S_IFDIR => 'd',
+        stat::S_IFLNK => 'l',
+        stat::S_IFIFO => 'p',
+        stat::S_IFCHR => 'c',
+        stat::S_IFBLK => 'b',
+        stat::S_IFSOCK => 's',
+        _ => 'f',
+    }
+}
+
+fn parse_line(line: &str) -> Result<(PathBuf, ManifestEntry), String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 6 && fields.len() != 7 {
+        return Err(format!("expected 6 or 7 fields, found {}", fields.len()));
+    }
+    let ifmt = match fields[0] {
+        "f" => stat::S_IFREG,
+        "d" => stat::S_IFDIR,
+        "l" => stat::S_IFLNK,
+        "p" => stat::S_IFIFO,
+        "c" => stat::S_IFCHR,
+        "b" => stat::S_IFBLK,
+        "s" => stat::S_IFSOCK,
+        other => return Err(format!("unknown type '{}'", other)),
+    };
+    let number = |name: &str, value: &str| format!("invalid {} '{}'", name, value);
+    let perm = u32::from_str_radix(fields[1], 8).map_err(|_| number("mode", fields[1]))?;
+    let entry = ManifestEntry {
+        mode: ifmt | perm,
+        size: fields[2].parse().map_err(|_| number("size", fields[2]))?,
+        mtime: fields[3].parse().map_err(|_| number("mtime", fields[3]))?,
+        digest: match fields[4] {
+            "-" => None,
+            digest => Some(digest.to_string()),
+        },
+        target: fields.get(6).map(|x| unescape(x)).transpose()?,
+    };
+    Ok((unescape(fields[5])?, entry))
+}
+
+/// Escape a path for one field of the text format.
+fn escape(path: &Path) -> String {
+    let mut out = String::new();
+    let bytes = os::fsencode(path);
+    let mut rest: &[u8] = &bytes;
+    while !rest.is_empty() {
+        let (valid, invalid) = match std::str::from_utf8(rest) {
+            Ok(s) => (s, &[][..]),
+            Err(err) => {
+                let (valid, invalid) = rest.split_at(err.valid_up_to());
+                let len = err.error_len().unwrap_or(invalid.len());
+                let valid = std::str::from_utf8(valid).expect("checked by from_utf8");
+                (valid, &invalid[..len])
+            }
+        };
+        for c in valid.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                c => out.push(c),
+            }
+        }
+        for b in invalid {
+            out.push_str(&format!("\\x{:02x}", b));
+        }
+        rest = &rest[valid.len() + invalid.len()..];
+    }
+    out
+}
+
+/// Undo `escape()`.
+fn unescape(s: &str) -> Result<PathBuf, String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some((_, '\\')) => bytes.push(b'\\'),
+            Some((_, 't')) => bytes.push(b'\t'),
+            Some((_, 'n')) => bytes.push(b'\n'),
+            Some((_, 'r')) => bytes.push(b'\r'),
+            Some((_, 'x')) => {
+                let hex = s.get(i + 2..i + 4).ok_or("truncated \\x escape")?;
+                // from_str_radix() would accept a sign, as in `\x+f`
+                if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err("invalid \\x escape".into());
+                }
+                let b = u8::from_str_radix(hex, 16).unwrap();
+                bytes.push(b);
+                chars.next();
+                chars.next();
+            }
+            _ => return Err(format!("invalid escape in '{}'", s)),
+        }
+    }
+    os::fsdecode(bytes).map_err(|err| err.to_string())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemFs;
+    use std::env;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_manifest() {
+        let mut tree = MemFs::new("/tree");
+        tree.add_file("docs/readme.txt", "hello")
+            .mode(0o600)
+            .mtime(1.5);
+        tree.add_file("tab\tand\\slash", "").mtime(2.0);
+        tree.add_symlink("latest", "docs/readme.txt").mtime(3.0);
+        tree.add_file(".git/HEAD", "ref");
+        tree.add_dir("docs").mode(0o750).mtime(4.0);
+
+        let manifest = ManifestBuilder::new("/tree").fs(tree).build().unwrap();
+        let paths: Vec<&Path> = manifest.entries.keys().map(PathBuf::as_path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("docs"),
+                Path::new("docs/readme.txt"),
+                Path::new("latest"),
+                Path::new("tab\tand\\slash")
+            ]
+        );
+        let text = manifest.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "filecmp-manifest 1");
+        assert_eq!(lines[1], "d\t0750\t0\t4\t-\tdocs");
+        assert_eq!(
+            lines[2],
+            "f\t0600\t5\t1.5\t\
+             2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824\t\
+             docs/readme.txt"
+        );
+        assert!(lines[3].ends_with("\t-\tlatest\tdocs/readme.txt"));
+        assert!(lines[4].ends_with("\ttab\\tand\\\\slash"));
+        assert_eq!(text.parse::<Manifest>().unwrap(), manifest);
+
+        assert_eq!(escape(Path::new("a\nb")), "a\\nb");
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let path = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9 \xff.txt"));
+            assert_eq!(escape(path), "caf\\xe9 \\xff.txt");
+            assert_eq!(unescape(&escape(path)).unwrap(), path);
+        }
+        assert!(unescape("\\x+f").is_err());
+        assert!("filecmp-manifest 2\n".parse::<Manifest>().is_err());
+        assert!("filecmp-manifest 1\nf\t0644\tbig\t0\t-\tx\n"
+            .parse::<Manifest>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_manifest_compare");
+        let _ = fs::remove_dir_all(&test_dir);
+        let dir = test_dir.join("tree");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for name in &[
+            "same.txt",
+            "touched.txt",
+            "edited.txt",
+            "gone.txt",
+            "sub/mode.txt",
+        ] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        let manifest_path = test_dir.join("tree.manifest");
+        fs::write(&manifest_path, Manifest::new(&dir).unwrap().to_string()).unwrap();
+
+        let later = SystemTime::now() + Duration::from_secs(60);
+        let touch = |name: &str| {
+            let file = fs::File::options()
+                .write(true)
+                .open(dir.join(name))
+                .unwrap();
+            file.set_modified(later).unwrap();
+        };
+        touch("touched.txt");
+        fs::write(dir.join("edited.txt"), "EDITED.TXT").unwrap();
+        touch("edited.txt");
+        fs::remove_file(dir.join("gone.txt")).unwrap();
+        fs::write(dir.join("new.txt"), "new").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let perms = fs::Permissions::from_mode(0o600);
+            fs::set_permissions(dir.join("sub").join("mode.txt"), perms).unwrap();
+        }
+
+        let x = compare(&dir, &manifest_path).unwrap();
+        assert_eq!(x.left_only, vec![PathBuf::from("new.txt")]);
+        assert_eq!(x.right_only, vec![PathBuf::from("gone.txt")]);
+        assert_eq!(x.diff_files, vec![PathBuf::from("edited.txt")]);
+        assert_eq!(
+            x.same_files,
+            vec![PathBuf::from("same.txt"), "touched.txt".into()]
+        );
+        assert!(x.meta_diffs[Path::new("touched.txt")].mtime.is_some());
+        assert!(x.meta_diffs[Path::new("touched.txt")].mode.is_none());
+        #[cfg(unix)]
+        assert_eq!(
+            x.subdirs[Path::new("sub")].meta_diffs[Path::new("mode.txt")].mode,
+            Some((0o600, 0o644))
+        );
+        assert!(x
+            .display(crate::format::Format::Text, false)
+            .to_string()
+            .contains("Differing metadata : ['edited.txt', 'touched.txt']"));
+    }
+}
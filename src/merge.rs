@@ -0,0 +1,281 @@
+//! Three-way comparison of two directories against their common base.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{cmp_sources, stat, DirCmpBuilder, Source, FOLLOW_SYMLINKS_DEFAULT};
+
+/// Three-way comparison of `ours` and `theirs` against `base`.
+///
+/// Built with `DirCmpBuilder::build_merge()`, where `a` is ours and `b` is
+/// theirs. Every file below any of the three roots is listed once, by its
+/// path relative to the roots, in one of:
+///  - unchanged: same in all three trees.
+///  - changed_ours: changed, added or deleted in ours only.
+///  - changed_theirs: changed, added or deleted in theirs only.
+///  - changed_both: changed, added or deleted identically in both.
+///  - conflicts: changed differently in ours and theirs.
+///  - funny: entries which are neither files nor directories, or could not
+///    be compared.
+///
+/// Directories are walked rather than listed, so a directory added on one
+/// side shows up as its added files. An entry replaced by one of another
+/// type counts as changed, and is listed itself; directories standing
+/// for it in some trees are walked as well, so that their files show up
+/// as added, deleted or in conflict.
+///
+/// # Example
+///
+/// ```rust
+/// use std::env;
+/// use std::fs;
+/// use std::path::PathBuf;
+/// use filecmp::DirCmpBuilder;
+///
+/// let temp_dir = env::temp_dir().join("filecmp_merge_example");
+/// for (tree, version) in &[("base", "1.0"), ("ours", "1.1"), ("theirs", "1.0")] {
+///     fs::create_dir_all(temp_dir.join(tree)).unwrap();
+///     fs::write(temp_dir.join(tree).join("VERSION"), version).unwrap();
+/// }
+///
+/// let x = DirCmpBuilder::new(temp_dir.join("ours"), temp_dir.join("theirs"))
+///     .shallow(false)
+///     .build_merge(temp_dir.join("base"))
+///     .unwrap();
+/// assert_eq!(x.changed_ours, vec![PathBuf::from("VERSION")]);
+/// assert!(x.conflicts.is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MergeCmp {
+    pub base: PathBuf,
+    pub ours: PathBuf,
+    pub theirs: PathBuf,
+    pub unchanged: Vec<PathBuf>,
+    pub changed_ours: Vec<PathBuf>,
+    pub changed_theirs: Vec<PathBuf>,
+    pub changed_both: Vec<PathBuf>,
+    pub conflicts: Vec<PathBuf>,
+    pub funny: Vec<PathBuf>,
+}
+
+/// The type of an entry in one of the three trees.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Missing,
+    Dir,
+    File,
+    Other,
+}
+
+const BASE: usize = 0;
+const OURS: usize = 1;
+const THEIRS: usize = 2;
+
+impl DirCmpBuilder {
+    /// Compare `a` (ours) and `b` (theirs) against their common `base`,
+    /// with the same ignore and hide lists and file comparison options.
+    ///
+    /// The base is read from the real file system, or as an archive if
+    /// `archives()` is on.
+    pub fn build_merge(&self, base: impl AsRef<Path>) -> io::Result<MergeCmp> {
+        let base = base.as_ref();
+        let sources = [
+            Source::open(&None, base, self.archives)?,
+            Source::open(&self.left_fs, &self.left, self.archives)?,
+            Source::open(&self.right_fs, &self.right, self.archives)?,
+        ];
+        let roots = [base, self.left.as_path(), self.right.as_path()];
+        let mut mc = MergeCmp {
+            base: base.to_path_buf(),
+            ours: self.left.clone(),
+            theirs: self.right.clone(),
+            ..MergeCmp::default()
+        };
+        self.merge_at(&sources, &roots, Path::new(""), [true; 3], &mut mc)?;
+        Ok(mc)
+    }
+
+    /// Classify the entries of directory `rel`, which is only present in
+    /// the trees flagged in `present`.
+    fn merge_at(
+        &self,
+        sources: &[Source; 3],
+        roots: &[&Path; 3],
+        rel: &Path,
+        present: [bool; 3],
+        mc: &mut MergeCmp,
+    ) -> io::Result<()> {
+        let mut lists = [Vec::new(), Vec::new(), Vec::new()];
+        for i in 0..3 {
+            if present[i] {
+                lists[i] = self.filter(sources[i].fs().read_dir(&roots[i].join(rel))?);
+            }
+        }
+        let names: BTreeSet<&PathBuf> = lists.iter().flatten().collect();
+
+        for name in names {
+            let x = rel.join(name);
+            let mut kinds = [Kind::Missing; 3];
+            for i in 0..3 {
+                if lists[i].binary_search(name).is_ok() {
+                    kinds[i] = match sources[i]
+                        .fs()
+                        .stat(&roots[i].join(&x), FOLLOW_SYMLINKS_DEFAULT)
+                    {
+                        Ok(st) if stat::S_ISDIR(st.st_mode) => Kind::Dir,
+                        Ok(st) if stat::S_ISREG(st.st_mode) => Kind::File,
+                        _ => Kind::Other,
+                    };
+                }
+            }
+            if kinds.contains(&Kind::Other) {
+                mc.funny.push(x);
+                continue;
+            }
+            let dirs = [
+                kinds[BASE] == Kind::Dir,
+                kinds[OURS] == Kind::Dir,
+                kinds[THEIRS] == Kind::Dir,
+            ];
+            if kinds.iter().all(|k| *k == Kind::Dir || *k == Kind::Missing) {
+                self.merge_at(sources, roots, &x, dirs, mc)?;
+                continue;
+            }
+
+            // a file on one side, a directory on another: the entry is
+            // compared by type, the directories are walked below
+            let same = |i: usize, j: usize| match (kinds[i], kinds[j]) {
+                (Kind::Missing, Kind::Missing) | (Kind::Dir, Kind::Dir) => Ok(true),
+                (Kind::File, Kind::File) => cmp_sources(
+                    &sources[i],
+                    &roots[i].join(&x),
+                    &sources[j],
+                    &roots[j].join(&x),
+                    &self.options,
+                ),
+                _ => Ok(false),
+            };
+            let outcome = (|| {
+                Ok::<_, io::Error>((same(BASE, OURS)?, same(BASE, THEIRS)?, same(OURS, THEIRS)?))
+            })();
+            let list = match outcome {
+                Ok((true, true, _)) => &mut mc.unchanged,
+                Ok((false, true, _)) => &mut mc.changed_ours,
+                Ok((true, false, _)) => &mut mc.changed_theirs,
+                Ok((false, false, true)) => &mut mc.changed_both,
+                Ok((false, false, false)) => &mut mc.conflicts,
+                Err(_) => &mut mc.funny,
+            };
+            list.push(x.clone());
+            if dirs.contains(&true) {
+                self.merge_at(sources, roots, &x, dirs, mc)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_merge() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_merge");
+        let _ = fs::remove_dir_all(&test_dir);
+        let write = |tree: &str, name: &str, data: &str| {
+            let path = test_dir.join(tree).join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        };
+        for tree in &["base", "ours", "theirs"] {
+            write(tree, "same.txt", "same");
+            write(tree, "lib/mod.rs", "mod");
+        }
+        write("base", "ours.txt", "v1");
+        write("ours", "ours.txt", "v2");
+        write("theirs", "ours.txt", "v1");
+        write("base", "theirs.txt", "v1");
+        write("theirs", "theirs.txt", "v2");
+        write("ours", "theirs.txt", "v1");
+        write("base", "both.txt", "v1");
+        write("ours", "both.txt", "v2");
+        write("theirs", "both.txt", "v2");
+        write("base", "conflict.txt", "v1");
+        write("ours", "conflict.txt", "v2");
+        write("theirs", "conflict.txt", "v3");
+        write("ours", "lib/added.rs", "ours");
+        write("theirs", "new/added.rs", "theirs");
+        write("base", "gone.txt", "gone");
+        write("base", "edited_gone.txt", "v1");
+        write("ours", "edited_gone.txt", "v2");
+
+        let x = DirCmpBuilder::new(test_dir.join("ours"), test_dir.join("theirs"))
+            .shallow(false)
+            .build_merge(test_dir.join("base"))
+            .unwrap();
+        let p = PathBuf::from;
+        assert_eq!(x.unchanged, vec![p("lib/mod.rs"), p("same.txt")]);
+        assert_eq!(x.changed_ours, vec![p("lib/added.rs"), p("ours.txt")]);
+        assert_eq!(x.changed_theirs, vec![p("new/added.rs"), p("theirs.txt")]);
+        assert_eq!(x.changed_both, vec![p("both.txt"), p("gone.txt")]);
+        assert_eq!(x.conflicts, vec![p("conflict.txt"), p("edited_gone.txt")]);
+        assert!(x.funny.is_empty());
+    }
+
+    #[test]
+    fn test_merge_type_conflicts() {
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_merge_type_conflicts");
+        let _ = fs::remove_dir_all(&test_dir);
+        let write = |tree: &str, name: &str, data: &str| {
+            let path = test_dir.join(tree).join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        };
+        for tree in &["base", "ours", "theirs"] {
+            fs::create_dir_all(test_dir.join(tree)).unwrap();
+        }
+        // a file replaced by a directory in ours only
+        write("base", "to_dir", "file");
+        write("ours", "to_dir/inner.txt", "inner");
+        write("theirs", "to_dir", "file");
+        // a directory replaced by a file in ours, while theirs edits in it
+        write("base", "to_file/kept.txt", "kept");
+        write("base", "to_file/edited.txt", "v1");
+        write("ours", "to_file", "file");
+        write("theirs", "to_file/kept.txt", "kept");
+        write("theirs", "to_file/edited.txt", "v2");
+        // added as a file in ours and as a directory in theirs
+        write("ours", "added", "file");
+        write("theirs", "added/inner.txt", "inner");
+        // replaced by the same file on both sides
+        write("base", "both/inner.txt", "inner");
+        write("ours", "both", "file");
+        write("theirs", "both", "file");
+
+        let x = DirCmpBuilder::new(test_dir.join("ours"), test_dir.join("theirs"))
+            .shallow(false)
+            .build_merge(test_dir.join("base"))
+            .unwrap();
+        let p = PathBuf::from;
+        assert!(x.unchanged.is_empty());
+        assert_eq!(
+            x.changed_ours,
+            vec![
+                p("to_dir"),
+                p("to_dir/inner.txt"),
+                p("to_file"),
+                p("to_file/kept.txt")
+            ]
+        );
+        assert_eq!(x.changed_theirs, vec![p("added/inner.txt")]);
+        assert_eq!(x.changed_both, vec![p("both"), p("both/inner.txt")]);
+        assert_eq!(x.conflicts, vec![p("added"), p("to_file/edited.txt")]);
+        assert!(x.funny.is_empty());
+    }
+}
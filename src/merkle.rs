@@ -0,0 +1,329 @@
+//! Merkle digests of directory trees.
+//!
+//! The digest of a directory is the SHA-256 of its sorted entries, each
+//! given by its name, its file type and the digest of its content: the
+//! SHA-256 of a regular file, of the target of a symbolic link, or the
+//! digest of a subdirectory. Trees with the same digest have the same
+//! names, types and contents, whatever their metadata.
+//!
+//! `fingerprint()` returns the digest of a whole tree, and
+//! `DirCmpBuilder::merkle()` skips common subdirectories whose digests
+//! would be equal, comparing them entry by entry only when the walk
+//! reaches them. File digests come from `FileSystem::digest()` when
+//! known, so trees served by a manifest are not read.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::env;
+//! use std::fs;
+//! use filecmp::merkle;
+//!
+//! let temp_dir = env::temp_dir().join("filecmp_merkle_example");
+//! for side in &["a", "b"] {
+//!     fs::create_dir_all(temp_dir.join(side).join("sub")).unwrap();
+//!     fs::write(temp_dir.join(side).join("sub").join("hello.txt"), b"hello").unwrap();
+//! }
+//!
+//! let a = merkle::fingerprint(temp_dir.join("a")).unwrap();
+//! assert_eq!(a, merkle::fingerprint(temp_dir.join("b")).unwrap());
+//! assert_eq!(a.len(), 64);
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    digest, filter_names, os, stat, DirCmpBuilder, Source, DEFAULT_HIDES, DEFAULT_IGNORES,
+    FOLLOW_SYMLINKS_DEFAULT,
+};
+
+/// Return the Merkle digest of the directory `dir` in lowercase hex, with
+/// the default ignore and hide lists and following symbolic links.
+pub fn fingerprint(dir: impl AsRef<Path>) -> io::Result<String> {
+    let hide: Vec<OsString> = DEFAULT_HIDES.iter().map(OsString::from).collect();
+    let ignore: Vec<OsString> = DEFAULT_IGNORES.iter().map(OsString::from).collect();
+    let walker = Walker {
+        src: &Source::Real,
+        root: dir.as_ref(),
+        hide: &hide,
+        ignore: &ignore,
+        follow_symlinks: FOLLOW_SYMLINKS_DEFAULT,
+        digest_cache: false,
+    };
+    walker.digest(Path::new(""), &mut HashMap::new())
+}
+
+/// Lazy comparison of common directories by their Merkle digests.
+///
+/// Rather than digesting both trees up front, a pair of directories is
+/// compared entry by entry when `DirCmpBuilder` reaches it, and the
+/// comparison stops at the first entry that differs. Files of different
+/// sizes are told apart without reading them, and pairs of subdirectories
+/// already compared are remembered, so that a walk below a differing
+/// directory does not compare them again.
+pub(crate) struct Digests<'a> {
+    left: Walker<'a>,
+    right: Walker<'a>,
+    known: RefCell<HashMap<(PathBuf, PathBuf), bool>>,
+}
+
+impl<'a> Digests<'a> {
+    pub(crate) fn new(
+        builder: &'a DirCmpBuilder,
+        left_src: &'a Source,
+        right_src: &'a Source,
+    ) -> Self {
+        let walker = |src, root| Walker {
+            src,
+            root,
+            hide: &builder.hide,
+            ignore: &builder.ignore,
+            follow_symlinks: builder.follow_symlinks,
+            digest_cache: builder.options.digest_cache,
+        };
+        Digests {
+            left: walker(left_src, &builder.left),
+            right: walker(right_src, &builder.right),
+            known: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the directories at `a_rel` on the left and `b_rel` on the
+    /// right would have the same digest. Directories with an entry which
+    /// cannot be read are never the same.
+    pub(crate) fn same(&self, a_rel: &Path, b_rel: &Path) -> bool {
+        let key = (a_rel.to_path_buf(), b_rel.to_path_buf());
+        if let Some(&same) = self.known.borrow().get(&key) {
+            return same;
+        }
+        let same = self.compare(a_rel, b_rel).unwrap_or(false);
+        self.known.borrow_mut().insert(key, same);
+        same
+    }
+
+    fn compare(&self, a_rel: &Path, b_rel: &Path) -> io::Result<bool> {
+        let left = self.left.names(a_rel)?;
+        if left != self.right.names(b_rel)? {
+            return Ok(false);
+        }
+        for name in &left {
+            let (a, b) = (a_rel.join(name), b_rel.join(name));
+            let (s1, s2) = (self.left.stat(&a)?, self.right.stat(&b)?);
+            let file_type = stat::S_IFMT(s1.st_mode);
+            let same = if file_type != stat::S_IFMT(s2.st_mode) {
+                false
+            } else if stat::S_ISDIR(file_type) {
+                self.same(&a, &b)
+            } else if stat::S_ISREG(file_type) {
+                s1.st_size == s2.st_size
+                    && self.left.content_digest(&a, file_type)?
+                        == self.right.content_digest(&b, file_type)?
+            } else {
+                self.left.content_digest(&a, file_type)?
+                    == self.right.content_digest(&b, file_type)?
+            };
+            if !same {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+struct Walker<'a> {
+    src: &'a Source,
+    root: &'a Path,
+    hide: &'a [OsString],
+    ignore: &'a [OsString],
+    follow_symlinks: bool,
+    /// Cache file digests in extended attributes, see
+    /// `CmpOptions::digest_cache()`.
+    digest_cache: bool,
+}
+
+impl Walker<'_> {
+    /// The names of the directory `rel`, without hidden or ignored ones.
+    fn names(&self, rel: &Path) -> io::Result<Vec<PathBuf>> {
+        let names = self.src.fs().read_dir(&self.root.join(rel))?;
+        Ok(filter_names(names, self.hide, self.ignore))
+    }
+
+    fn stat(&self, rel: &Path) -> io::Result<os::StatResult> {
+        self.src
+            .fs()
+            .stat(&self.root.join(rel), self.follow_symlinks)
+    }
+
+    /// Digest the directory `rel`, recording it and its subdirectories in
+    /// `dirs`. An unreadable entry fails the digest once the others are
+    /// walked.
+    fn digest(&self, rel: &Path, dirs: &mut HashMap<PathBuf, String>) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        let mut result = Ok(());
+        for name in self.names(rel)? {
+            let path = rel.join(&name);
+            match self.entry_digest(&path, dirs) {
+                Ok((file_type, digest)) => {
+                    hasher.update(os::fsencode(&name));
+                    hasher.update(format!("\0{:o} {}\n", file_type, digest));
+                }
+                Err(err) => result = Err(err),
+            }
+        }
+        result?;
+        let digest = digest::hex(&hasher.finalize());
+        dirs.insert(rel.to_path_buf(), digest.clone());
+        Ok(digest)
+    }
+
+    /// The file type and content digest of one entry.
+    fn entry_digest(
+        &self,
+        rel: &Path,
+        dirs: &mut HashMap<PathBuf, String>,
+    ) -> io::Result<(u32, String)> {
+        let file_type = stat::S_IFMT(self.stat(rel)?.st_mode);
+        let digest = if stat::S_ISDIR(file_type) {
+            self.digest(rel, dirs)?
+        } else {
+            self.content_digest(rel, file_type)?
+        };
+        Ok((file_type, digest))
+    }
+
+    /// The content digest of an entry other than a directory.
+    fn content_digest(&self, rel: &Path, file_type: u32) -> io::Result<String> {
+        let path = self.root.join(rel);
+        if stat::S_ISREG(file_type) {
+            self.src.sha256(&path, self.digest_cache)
+        } else if stat::S_ISLNK(file_type) {
+            digest::sha256(&os::fsencode(&self.src.fs().read_link(&path)?)[..])
+        } else {
+            Ok(String::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::{FileSystem, MemFs};
+    use crate::DirCmpBuilder;
+    use std::env;
+    use std::fs;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    /// A `MemFs` recording the files opened.
+    #[derive(Debug)]
+    struct Opened(MemFs, Arc<Mutex<Vec<PathBuf>>>);
+
+    impl FileSystem for Opened {
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            self.0.read_dir(path)
+        }
+
+        fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<os::StatResult> {
+            self.0.stat(path, follow_symlinks)
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+            self.1.lock().unwrap().push(path.to_path_buf());
+            self.0.open(path)
+        }
+    }
+
+    #[test]
+    fn test_merkle() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_merkle");
+        let _ = fs::remove_dir_all(&test_dir);
+        let (a, b) = (test_dir.join("a"), test_dir.join("b"));
+        for dir in &[&a, &b] {
+            fs::create_dir_all(dir.join("same").join("deep")).unwrap();
+            fs::create_dir_all(dir.join("changed")).unwrap();
+            fs::write(dir.join("same").join("deep").join("file"), "same").unwrap();
+            fs::write(dir.join("same").join("CVS"), "ignored").unwrap();
+        }
+        fs::write(a.join("same").join("CVS"), "ignored too").unwrap();
+        fs::write(a.join("changed").join("file"), "left").unwrap();
+        fs::write(b.join("changed").join("file"), "right").unwrap();
+
+        let (fa, fb) = (fingerprint(&a).unwrap(), fingerprint(&b).unwrap());
+        assert_ne!(fa, fb);
+        assert_eq!(
+            fingerprint(a.join("same")).unwrap(),
+            fingerprint(b.join("same")).unwrap()
+        );
+        // names are part of the digest, not only contents
+        fs::rename(b.join("same").join("deep"), b.join("same").join("moved")).unwrap();
+        assert_ne!(
+            fingerprint(a.join("same")).unwrap(),
+            fingerprint(b.join("same")).unwrap()
+        );
+        fs::rename(b.join("same").join("moved"), b.join("same").join("deep")).unwrap();
+
+        let x = DirCmpBuilder::new(&a, &b).merkle(true).build().unwrap();
+        assert_eq!(x.common_dirs.len(), 2);
+        assert_eq!(x.same_dirs, vec![PathBuf::from("same")]);
+        assert!(!x.subdirs.contains_key(Path::new("same")));
+        assert_eq!(
+            x.subdirs[Path::new("changed")].diff_files,
+            vec![PathBuf::from("file")]
+        );
+
+        let x = DirCmpBuilder::new(&a, &b).build().unwrap();
+        assert!(x.same_dirs.is_empty());
+        assert_eq!(x.subdirs.len(), 2);
+
+        // metadata is not in the digests, so equal subdirectories are walked
+        let file = fs::File::options()
+            .write(true)
+            .open(b.join("same").join("deep").join("file"))
+            .unwrap();
+        file.set_modified(std::time::UNIX_EPOCH).unwrap();
+        let x = DirCmpBuilder::new(&a, &b)
+            .merkle(true)
+            .metadata(true)
+            .build()
+            .unwrap();
+        assert!(x.same_dirs.is_empty());
+        let deep = &x.subdirs[Path::new("same")].subdirs[Path::new("deep")];
+        assert!(deep.meta_diffs[Path::new("file")].mtime.is_some());
+    }
+
+    #[test]
+    fn test_merkle_lazy() {
+        let opened = Arc::new(Mutex::new(Vec::new()));
+        let mut left = MemFs::new("a");
+        left.add_file("only/file", "left only");
+        left.add_file("same/file", "same");
+        left.add_file("changed/a", "left");
+        left.add_file("changed/b", "unread");
+        let mut right = MemFs::new("b");
+        right.add_file("same/file", "same");
+        right.add_file("changed/a", "right!");
+        right.add_file("changed/b", "unread");
+
+        let x = DirCmpBuilder::new("a", "b")
+            .left_fs(Opened(left, opened.clone()))
+            .right_fs(Opened(right, opened.clone()))
+            .merkle(true)
+            .build()
+            .unwrap();
+        assert_eq!(x.same_dirs, vec![PathBuf::from("same")]);
+        assert_eq!(
+            x.subdirs[Path::new("changed")].diff_files,
+            vec![PathBuf::from("a")]
+        );
+        // left-only trees are not digested, and a differing size is enough
+        let opened = opened.lock().unwrap();
+        assert!(!opened.iter().any(|p| p.starts_with("a/only")));
+        assert!(!opened.iter().any(|p| p.ends_with("changed/a")));
+    }
+}
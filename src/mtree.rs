@@ -0,0 +1,763 @@
+//! BSD mtree(5) specifications.
+//!
+//! `Spec` reads the keywords `type`, `mode`, `uid`, `gid`, `size`, `time`,
+//! `sha256digest` and `link` of a specification, in the hierarchical form
+//! written by `mtree -c` as well as the full path form of `mtree -C` and
+//! libarchive, including `/set` and `/unset`. Other keywords are skipped.
+//! Specifications are written in the full path form, one line per entry.
+//!
+//! A tree is verified against a specification with `Spec::compare()`,
+//! which checks the keywords present for each entry and returns a `DirCmp`.
+//! It serves the specification through `SpecFs` as the right side of a
+//! `DirCmpBuilder`; `Spec::builder()` returns that builder to set other
+//! options first, such as the ignore and hide lists.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::env;
+//! use std::fs;
+//! use std::path::Path;
+//! use filecmp::mtree::Spec;
+//!
+//! let temp_dir = env::temp_dir().join("filecmp_mtree_example");
+//! fs::create_dir_all(temp_dir.join("etc")).unwrap();
+//! fs::write(temp_dir.join("etc").join("motd"), b"hello").unwrap();
+//!
+//! let spec: Spec = "#mtree\n\
+//!                   . type=dir\n\
+//!                   ./etc type=dir\n\
+//!                   ./etc/motd type=file size=5\n"
+//!     .parse()
+//!     .unwrap();
+//! assert_eq!(spec.entries[Path::new("etc/motd")].size, Some(5));
+//! let x = spec.compare(&temp_dir, "motd.mtree").unwrap();
+//! assert_eq!(x.subdirs[Path::new("etc")].same_files.len(), 1);
+//! ```
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::os::{self, StatResult};
+use crate::vfs::{self, FileSystem, RealFs};
+use crate::{digest, filter_names, stat, DirCmp, DirCmpBuilder, DEFAULT_HIDES, DEFAULT_IGNORES};
+
+/// Values of the `type` keyword, like the file type table of
+/// `stat::filemode()`.
+const TYPES: [(u32, &str); 7] = [
+    (stat::S_IFBLK, "block"),
+    (stat::S_IFCHR, "char"),
+    (stat::S_IFDIR, "dir"),
+    (stat::S_IFIFO, "fifo"),
+    (stat::S_IFREG, "file"),
+    (stat::S_IFLNK, "link"),
+    (stat::S_IFSOCK, "socket"),
+];
+
+/// An mtree specification, keyed by paths relative to its root.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spec {
+    pub entries: BTreeMap<PathBuf, SpecEntry>,
+}
+
+/// The keywords of one entry of a `Spec`; those not given are not checked.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpecEntry {
+    /// File type, as the S_IFMT bits of st_mode.
+    pub file_type: Option<u32>,
+    /// Permission bits.
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub size: Option<u64>,
+    /// Modification time as seconds and nanoseconds since the epoch.
+    pub time: Option<(i64, u32)>,
+    /// Lowercase hex SHA-256 digest.
+    pub sha256: Option<String>,
+    /// Target of a symbolic link.
+    pub link: Option<PathBuf>,
+}
+
+impl Spec {
+    /// Describe the directory `root` with all keywords, with the default
+    /// ignore and hide lists. Symbolic links are described, not followed.
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        SpecBuilder::new(root).build()
+    }
+
+    /// Verify the directory `dir` against the specification, as the left
+    /// side of a `DirCmp` whose right side is named `right` in reports,
+    /// with the default ignore and hide lists.
+    ///
+    /// Entries only found in `dir` are in `left_only`, missing ones in
+    /// `right_only`. Files are the same if their size, digest and link
+    /// target match the keywords given; type mismatches are common funny
+    /// cases. Permission, time and owner mismatches are in `meta_diffs`.
+    pub fn compare(&self, dir: impl AsRef<Path>, right: impl AsRef<Path>) -> io::Result<DirCmp> {
+        self.clone().builder(dir, right).build()
+    }
+
+    /// A `DirCmpBuilder` verifying `dir` like `compare()`, to set other
+    /// options such as the ignore and hide lists before building.
+    pub fn builder(self, dir: impl AsRef<Path>, right: impl AsRef<Path>) -> DirCmpBuilder {
+        let dir = dir.as_ref();
+        DirCmpBuilder::new(dir, &right)
+            .right_fs(SpecFs::new(&right, self).base(dir))
+            .shallow(false)
+            .follow_symlinks(false)
+            .metadata(true)
+            .owners(true)
+    }
+}
+
+/// Builder of a `Spec`, with the ignore and hide lists of `DirCmpBuilder`.
+#[derive(Debug, Clone)]
+pub struct SpecBuilder {
+    root: PathBuf,
+    ignore: Vec<OsString>,
+    hide: Vec<OsString>,
+}
+
+impl SpecBuilder {
+    /// Create a builder describing the directory `root`.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        SpecBuilder {
+            root: root.as_ref().to_path_buf(),
+            ignore: DEFAULT_IGNORES.iter().map(OsString::from).collect(),
+            hide: DEFAULT_HIDES.iter().map(OsString::from).collect(),
+        }
+    }
+
+    /// Names to ignore, replacing DEFAULT_IGNORES.
+    pub fn ignore<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.ignore = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Names to hide, replacing DEFAULT_HIDES.
+    pub fn hide<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.hide = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Walk the tree and digest its files. Symbolic links are described,
+    /// not followed.
+    pub fn build(&self) -> io::Result<Spec> {
+        let mut spec = Spec::default();
+        self.build_at(Path::new(""), &mut spec)?;
+        Ok(spec)
+    }
+
+    fn build_at(&self, rel: &Path, spec: &mut Spec) -> io::Result<()> {
+        let dir = self.root.join(rel);
+        for name in filter_names(RealFs.read_dir(&dir)?, &self.hide, &self.ignore) {
+            let path = dir.join(&name);
+            let st = os::stat(&path, false)?;
+            let mut entry = SpecEntry {
+                file_type: Some(stat::S_IFMT(st.st_mode)),
+                mode: Some(stat::S_IMODE(st.st_mode)),
+                uid: Some(st.st_uid),
+                gid: Some(st.st_gid),
+                size: None,
+                time: Some(mtime(&path)?),
+                sha256: None,
+                link: None,
+            };
+            if stat::S_ISREG(st.st_mode) {
+                entry.size = Some(st.st_size);
+                entry.sha256 = Some(digest::sha256(RealFs.open(&path)?)?);
+            } else if stat::S_ISLNK(st.st_mode) {
+                entry.link = Some(RealFs.read_link(&path)?);
+            }
+            spec.entries.insert(rel.join(&name), entry);
+            if stat::S_ISDIR(st.st_mode) {
+                self.build_at(&rel.join(&name), spec)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A `Spec` served as a file system rooted at `root`.
+///
+/// Entries have the keywords given and no content: `open()` fails, and
+/// comparisons use `digest()` instead. Keywords which are not given are
+/// 0, or a regular file for `type`, unless a base directory is set with
+/// `base()`: they are then taken from the same entry below it, so that
+/// only the keywords given are verified.
+#[derive(Debug, Clone)]
+pub struct SpecFs {
+    root: PathBuf,
+    spec: Spec,
+    base: Option<PathBuf>,
+}
+
+impl SpecFs {
+    /// Serve `spec` with its entries below `root`.
+    pub fn new(root: impl AsRef<Path>, spec: Spec) -> Self {
+        SpecFs {
+            root: root.as_ref().to_path_buf(),
+            spec,
+            base: None,
+        }
+    }
+
+    /// Take the keywords not given from the entries below `dir`.
+    pub fn base(mut self, dir: impl AsRef<Path>) -> Self {
+        self.base = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    fn resolve(&self, path: &Path, follow_symlinks: bool) -> io::Result<PathBuf> {
+        let entries = &self.spec.entries;
+        vfs::resolve(&self.root, path, follow_symlinks, |rel| {
+            entries.get(rel).map(|entry| entry.link.as_deref())
+        })
+    }
+
+    /// Find the entry at `path` and its path relative to the root; `None`
+    /// for the root directory.
+    fn entry(
+        &self,
+        path: &Path,
+        follow_symlinks: bool,
+    ) -> io::Result<(PathBuf, Option<&SpecEntry>)> {
+        let rel = self.resolve(path, follow_symlinks)?;
+        let entry = self.spec.entries.get(&rel);
+        Ok((rel, entry))
+    }
+
+    /// The entry at `rel` below the base directory, if there is one.
+    fn base_path(&self, rel: &Path) -> Option<PathBuf> {
+        self.base.as_ref().map(|base| base.join(rel))
+    }
+}
+
+impl FileSystem for SpecFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let dir = self.resolve(path, true)?;
+        let entries = &self.spec.entries;
+        if let Some(entry) = entries.get(&dir) {
+            if entry.file_type.is_some_and(|t| t != stat::S_IFDIR) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}: not a directory", path.display()),
+                ));
+            }
+        }
+        // the entries below dir sort right after it
+        Ok(entries
+            .range(dir.clone()..)
+            .take_while(|(name, _)| name.starts_with(&dir))
+            .filter(|(name, _)| name.parent() == Some(&dir))
+            .filter_map(|(name, _)| name.file_name().map(PathBuf::from))
+            .collect())
+    }
+
+    fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<StatResult> {
+        let (rel, entry) = self.entry(path, follow_symlinks)?;
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return Ok(stat_result(stat::S_IFDIR | 0o755, 0, 0, 0, 0.0)),
+        };
+        let base = self
+            .base_path(&rel)
+            .and_then(|base| os::stat(base, follow_symlinks).ok());
+        let given = |value: Option<u32>, found: fn(&StatResult) -> u32, default| {
+            value
+                .or_else(|| base.as_ref().map(found))
+                .unwrap_or(default)
+        };
+        let file_type = given(
+            entry.file_type,
+            |st| stat::S_IFMT(st.st_mode),
+            stat::S_IFREG,
+        );
+        let mode = given(entry.mode, |st| stat::S_IMODE(st.st_mode), 0);
+        let uid = given(entry.uid, |st| st.st_uid, 0);
+        let gid = given(entry.gid, |st| st.st_gid, 0);
+        let size = entry
+            .size
+            .or_else(|| base.as_ref().map(|st| st.st_size))
+            .unwrap_or(0);
+        let mtime = match entry.time {
+            Some(time) => seconds(time),
+            None => base.as_ref().map_or(0.0, |st| st.st_mtime),
+        };
+        Ok(stat_result(file_type | mode, uid, gid, size, mtime))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let (rel, entry) = self.entry(path, true)?;
+        match self.base_path(&rel) {
+            Some(base) if entry.is_some_and(|entry| entry.sha256.is_none()) => RealFs.open(&base),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{}: specifications have no file content", path.display()),
+            )),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let (rel, entry) = self.entry(path, false)?;
+        match (
+            entry.and_then(|entry| entry.link.clone()),
+            self.base_path(&rel),
+        ) {
+            (Some(link), _) => Ok(link),
+            (None, Some(base)) if entry.is_some() => RealFs.read_link(&base),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: not a symbolic link", path.display()),
+            )),
+        }
+    }
+
+    fn digest(&self, path: &Path) -> io::Result<Option<String>> {
+        let (_, entry) = self.entry(path, true)?;
+        Ok(entry.and_then(|entry| entry.sha256.clone()))
+    }
+}
+
+fn stat_result(mode: u32, uid: u32, gid: u32, size: u64, mtime: f64) -> StatResult {
+    StatResult {
+        st_mode: mode,
+        st_ino: 0,
+        st_dev: 0,
+        st_nlink: 1,
+        st_uid: uid,
+        st_gid: gid,
+        st_size: size,
+        st_atime: mtime,
+        st_mtime: mtime,
+        st_ctime: mtime,
+    }
+}
+
+/// A `time` keyword in seconds since the epoch, rounded like the
+/// `st_mtime` of `os::stat()`.
+fn seconds((secs, nanos): (i64, u32)) -> f64 {
+    if secs >= 0 {
+        Duration::new(secs as u64, nanos).as_secs_f64()
+    } else if nanos == 0 {
+        -Duration::new(secs.unsigned_abs(), 0).as_secs_f64()
+    } else {
+        -Duration::new(secs.unsigned_abs() - 1, 1_000_000_000 - nanos).as_secs_f64()
+    }
+}
+
+/// Modification time of `path`, not following links, as seconds and
+/// nanoseconds since the epoch.
+fn mtime(path: &Path) -> io::Result<(i64, u32)> {
+    let modified = fs::symlink_metadata(path)?.modified()?;
+    Ok(match modified.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        Err(err) => {
+            let d = err.duration();
+            match d.subsec_nanos() {
+                0 => (-(d.as_secs() as i64), 0),
+                nanos => (-(d.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    })
+}
+
+/// Writes the full path form, starting with `#mtree` and the root.
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#mtree")?;
+        writeln!(f, ". type=dir")?;
+        for (path, entry) in &self.entries {
+            write!(f, "./{}", encode(path))?;
+            if let Some(file_type) = entry.file_type {
+                if let Some((_, name)) = TYPES.iter().find(|(t, _)| *t == file_type) {
+                    write!(f, " type={}", name)?;
+                }
+            }
+            if let Some(uid) = entry.uid {
+                write!(f, " uid={}", uid)?;
+            }
+            if let Some(gid) = entry.gid {
+                write!(f, " gid={}", gid)?;
+            }
+            if let Some(mode) = entry.mode {
+                write!(f, " mode={:04o}", mode)?;
+            }
+            if let Some(size) = entry.size {
+                write!(f, " size={}", size)?;
+            }
+            if let Some((secs, nanos)) = entry.time {
+                write!(f, " time={}.{:09}", secs, nanos)?;
+            }
+            if let Some(sha256) = &entry.sha256 {
+                write!(f, " sha256digest={}", sha256)?;
+            }
+            if let Some(link) = &entry.link {
+                write!(f, " link={}", encode(link))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads both the hierarchical and the full path form, failing with
+/// `InvalidData` on malformed lines.
+impl FromStr for Spec {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let mut spec = Spec::default();
+        let mut defaults = SpecEntry::default();
+        // the current directory of the hierarchical form, None above "."
+        let mut cwd: Option<PathBuf> = None;
+        let mut line = String::new();
+        for (i, raw) in s.lines().enumerate() {
+            if let Some(start) = raw.strip_suffix('\\') {
+                line.push_str(start);
+                line.push(' ');
+                continue;
+            }
+            line.push_str(raw);
+            let result = parse_line(&line, &mut spec, &mut defaults, &mut cwd);
+            line.clear();
+            result.map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", i + 1, err),
+                )
+            })?;
+        }
+        Ok(spec)
+    }
+}
+
+fn parse_line(
+    line: &str,
+    spec: &mut Spec,
+    defaults: &mut SpecEntry,
+    cwd: &mut Option<PathBuf>,
+) -> Result<(), String> {
+    let mut words = line.split_whitespace();
+    let first = match words.next() {
+        Some(first) if !first.starts_with('#') => first,
+        _ => return Ok(()),
+    };
+    match first {
+        "/set" => {
+            for word in words {
+                set_keyword(defaults, word)?;
+            }
+            return Ok(());
+        }
+        "/unset" => {
+            for word in words {
+                unset_keyword(defaults, word);
+            }
+            return Ok(());
+        }
+        ".." => {
+            match cwd {
+                Some(dir) if !dir.as_os_str().is_empty() => {
+                    dir.pop();
+                }
+                _ => *cwd = None,
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let mut entry = defaults.clone();
+    for word in words {
+        set_keyword(&mut entry, word)?;
+    }
+    let name = decode(first)?;
+    let full = first.contains('/');
+    let path = if full {
+        let rel: PathBuf = name
+            .components()
+            .filter(|c| !matches!(c, std::path::Component::CurDir))
+            .collect();
+        rel
+    } else if name == Path::new(".") {
+        PathBuf::new()
+    } else {
+        cwd.clone().unwrap_or_default().join(&name)
+    };
+    let is_dir = entry.file_type == Some(stat::S_IFDIR);
+    if !full && is_dir {
+        *cwd = Some(path.clone());
+    }
+    if !path.as_os_str().is_empty() {
+        spec.entries.insert(path, entry);
+    }
+    Ok(())
+}
+
+fn set_keyword(entry: &mut SpecEntry, word: &str) -> Result<(), String> {
+    let (key, value) = match word.split_once('=') {
+        Some(pair) => pair,
+        // flags such as "optional" or "nochange"
+        None => return Ok(()),
+    };
+    let invalid = || format!("invalid {} '{}'", key, value);
+    match key {
+        "type" => {
+            let file_type = TYPES.iter().find(|(_, name)| *name == value);
+            entry.file_type = Some(file_type.ok_or_else(invalid)?.0);
+        }
+        "mode" => entry.mode = Some(u32::from_str_radix(value, 8).map_err(|_| invalid())?),
+        "uid" => entry.uid = Some(value.parse().map_err(|_| invalid())?),
+        "gid" => entry.gid = Some(value.parse().map_err(|_| invalid())?),
+        "size" => entry.size = Some(value.parse().map_err(|_| invalid())?),
+        "time" => entry.time = Some(parse_time(value).ok_or_else(invalid)?),
+        "sha256" | "sha256digest" => entry.sha256 = Some(value.to_ascii_lowercase()),
+        "link" => entry.link = Some(decode(value)?),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Parse `seconds.nanoseconds`, where both parts are integers as written
+/// by mtree(8): `1.5` is five nanoseconds past the second.
+fn parse_time(value: &str) -> Option<(i64, u32)> {
+    let (secs, nanos) = value.split_once('.').unwrap_or((value, "0"));
+    if nanos.is_empty() || !nanos.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos: u32 = nanos.parse().ok()?;
+    if nanos >= 1_000_000_000 {
+        return None;
+    }
+    Some((secs.parse().ok()?, nanos))
+}
+
+fn unset_keyword(entry: &mut SpecEntry, key: &str) {
+    match key {
+        "all" => *entry = SpecEntry::default(),
+        "type" => entry.file_type = None,
+        "mode" => entry.mode = None,
+        "uid" => entry.uid = None,
+        "gid" => entry.gid = None,
+        "size" => entry.size = None,
+        "time" => entry.time = None,
+        "sha256" | "sha256digest" => entry.sha256 = None,
+        "link" => entry.link = None,
+        _ => {}
+    }
+}
+
+/// Encode a name like strsvis(3) with VIS_OCTAL: whitespace, backslashes,
+/// comment and glob characters and bytes outside printable ASCII become
+/// `\ooo`.
+fn encode(path: &Path) -> String {
+    let mut out = String::new();
+    for b in os::fsencode(path) {
+        if b.is_ascii_graphic() && !matches!(b, b'\\' | b'#' | b'*' | b'?' | b'[') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("\\{:03o}", b));
+        }
+    }
+    out
+}
+
+/// Undo `encode()`, also accepting the C-style escapes of strsvis(3).
+fn decode(s: &str) -> Result<PathBuf, String> {
+    let invalid = || format!("invalid escape in '{}'", s);
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        let (&c, tail) = rest.split_first().ok_or_else(invalid)?;
+        rest = tail;
+        match c {
+            b'0'..=b'7' => {
+                let digits = s.len() - rest.len() - 1;
+                let octal = s.get(digits..digits + 3).ok_or_else(invalid)?;
+                bytes.push(u8::from_str_radix(octal, 8).map_err(|_| invalid())?);
+                rest = &rest[2..];
+            }
+            b's' => bytes.push(b' '),
+            b't' => bytes.push(b'\t'),
+            b'n' => bytes.push(b'\n'),
+            b'r' => bytes.push(b'\r'),
+            b'\\' | b'#' => bytes.push(c),
+            _ => return Err(invalid()),
+        }
+    }
+    os::fsdecode(bytes).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_mtree() {
+        let spec: Spec = "#mtree v1.0\n\
+                          /set type=file uid=0 gid=0 mode=0644\n\
+                          .               type=dir mode=0755\n\
+                          \x20   bin         type=dir\n\
+                          \x20       sh      mode=0755 size=3 \\\n\
+                          \x20               sha256digest=ABC\n\
+                          \x20   ..\n\
+                          \x20   my\\040file  time=1.5\n\
+                          \x20   latest      type=link link=my\\040file\n\
+                          ./etc/motd type=file size=5 nochange\n\
+                          ..\n"
+            .parse()
+            .unwrap();
+        let paths: Vec<&Path> = spec.entries.keys().map(PathBuf::as_path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("bin"),
+                Path::new("bin/sh"),
+                Path::new("etc/motd"),
+                Path::new("latest"),
+                Path::new("my file"),
+            ]
+        );
+        let sh = &spec.entries[Path::new("bin/sh")];
+        assert_eq!(sh.file_type, Some(stat::S_IFREG));
+        assert_eq!(sh.mode, Some(0o755));
+        assert_eq!(sh.uid, Some(0));
+        assert_eq!(sh.sha256.as_deref(), Some("abc"));
+        assert_eq!(spec.entries[Path::new("my file")].time, Some((1, 5)));
+        assert_eq!(
+            spec.entries[Path::new("latest")].link.as_deref(),
+            Some(Path::new("my file"))
+        );
+        assert_eq!(spec.to_string().parse::<Spec>().unwrap(), spec);
+        assert!(spec
+            .to_string()
+            .contains("./my\\040file type=file uid=0 gid=0 mode=0644 time=1.000000005\n"));
+        assert!("./x mode=rw\n".parse::<Spec>().is_err());
+        assert!("./x time=1.+5\n".parse::<Spec>().is_err());
+        assert!("./x time=1.1000000000\n".parse::<Spec>().is_err());
+
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_mtree");
+        let _ = fs::remove_dir_all(&test_dir);
+        let dir = test_dir.join("tree");
+        fs::create_dir_all(dir.join("etc")).unwrap();
+        fs::write(dir.join("etc").join("motd"), "hello").unwrap();
+        fs::write(dir.join("etc").join("hosts"), "localhost").unwrap();
+        fs::write(dir.join("gone"), "gone").unwrap();
+        let spec = Spec::new(&dir).unwrap();
+        let x = spec.compare(&dir, "spec").unwrap();
+        assert!(x.left_only.is_empty() && x.right_only.is_empty());
+        assert!(x.meta_diffs.is_empty());
+        assert_eq!(x.subdirs[Path::new("etc")].same_files.len(), 2);
+
+        fs::write(dir.join("etc").join("motd"), "HELLO").unwrap();
+        fs::remove_file(dir.join("gone")).unwrap();
+        fs::write(dir.join("new"), "new").unwrap();
+        let x = spec.compare(&dir, "spec").unwrap();
+        assert_eq!(x.left_only, vec![PathBuf::from("new")]);
+        assert_eq!(x.right_only, vec![PathBuf::from("gone")]);
+        assert_eq!(
+            x.subdirs[Path::new("etc")].diff_files,
+            vec![PathBuf::from("motd")]
+        );
+
+        let mut spec = spec;
+        let hosts = spec.entries.get_mut(Path::new("etc/hosts")).unwrap();
+        hosts.uid = Some(4242);
+        let (secs, nanos) = hosts.time.unwrap();
+        hosts.time = Some((secs + 1, nanos));
+        let x = spec.compare(&dir, "spec").unwrap();
+        let hosts = x.subdirs[Path::new("etc")].meta_diffs[Path::new("hosts")];
+        assert_eq!(hosts.uid.map(|(_, expected)| expected), Some(4242));
+        let (found, expected) = hosts.mtime.unwrap();
+        assert!((expected - found - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_spec_fs() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_spec_fs");
+        let _ = fs::remove_dir_all(&test_dir);
+        let dir = test_dir.join("tree");
+        fs::create_dir_all(dir.join("skip")).unwrap();
+        fs::write(dir.join("a.txt"), "hello").unwrap();
+        fs::write(dir.join("skip").join("x"), "x").unwrap();
+        let parse = |text: &str| text.parse::<Spec>().unwrap();
+
+        // keywords not given are not verified
+        let spec = parse(
+            "./a.txt size=5
+./skip type=dir
+./skip/x
+",
+        );
+        let x = spec.compare(&dir, "spec").unwrap();
+        assert_eq!(x.same_files, vec![PathBuf::from("a.txt")]);
+        assert!(x.meta_diffs.is_empty());
+        assert_eq!(x.subdirs[Path::new("skip")].same_files.len(), 1);
+
+        let x = parse(
+            "./a.txt size=5 sha256digest=0123
+",
+        )
+        .compare(&dir, "spec")
+        .unwrap();
+        assert_eq!(x.diff_files, vec![PathBuf::from("a.txt")]);
+        assert_eq!(x.left_only, vec![PathBuf::from("skip")]);
+        let x = parse(
+            "./a.txt type=dir
+",
+        )
+        .compare(&dir, "spec")
+        .unwrap();
+        assert_eq!(x.common_funny, vec![PathBuf::from("a.txt")]);
+
+        // with the options of DirCmpBuilder
+        let x = parse(
+            "./a.txt mode=0600
+",
+        )
+        .builder(&dir, "spec")
+        .ignore(vec!["skip"])
+        .build()
+        .unwrap();
+        assert!(x.left_only.is_empty());
+        assert!(x.meta_diffs[Path::new("a.txt")].mode.is_some());
+
+        // without a base, keywords not given are 0
+        let spec_fs = SpecFs::new(
+            "spec",
+            parse(
+                "./a.txt size=5
+",
+            ),
+        );
+        let st = spec_fs.stat(Path::new("spec/a.txt"), false).unwrap();
+        assert_eq!((st.st_mode, st.st_size), (stat::S_IFREG, 5));
+        assert!(spec_fs.open(Path::new("spec/a.txt")).is_err());
+        assert_eq!(
+            spec_fs.read_dir(Path::new("spec")).unwrap(),
+            vec![PathBuf::from("a.txt")]
+        );
+    }
+}
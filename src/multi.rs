@@ -0,0 +1,358 @@
+//! N-way comparison of many directories at once.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::vfs::{FileSystem, RealFs};
+use crate::{
+    cmp_with, filter_names, os, stat, CmpOptions, PyList, DEFAULT_HIDES, DEFAULT_IGNORES,
+    FOLLOW_SYMLINKS_DEFAULT,
+};
+
+/// Comparison of any number of directory trees.
+///
+/// Every path found below any root is listed in `entries`, by its path
+/// relative to the roots. Trees are referred to by their index in `roots`.
+/// Files are compared to one representative of each equivalence class
+/// found so far, rather than pairwise.
+///
+/// # Example
+///
+/// ```rust
+/// use std::env;
+/// use std::fs;
+/// use std::path::Path;
+/// use filecmp::multi::MultiCmp;
+///
+/// let temp_dir = env::temp_dir().join("filecmp_multi_example");
+/// let hosts: Vec<_> = (0..3).map(|i| temp_dir.join(format!("host{}", i))).collect();
+/// for (host, output) in hosts.iter().zip(&["deterministic", "deterministic", "drift"]) {
+///     fs::create_dir_all(host).unwrap();
+///     fs::write(host.join("app.bin"), output).unwrap();
+/// }
+///
+/// let x = MultiCmp::new(&hosts).unwrap();
+/// let entry = &x.entries[Path::new("app.bin")];
+/// assert_eq!(entry.classes, vec![vec![0, 1], vec![2]]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiCmp {
+    pub roots: Vec<PathBuf>,
+    pub entries: BTreeMap<PathBuf, MultiEntry>,
+}
+
+/// How a path compares across the trees of a `MultiCmp`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultiEntry {
+    /// Trees which contain the path.
+    pub present: Vec<usize>,
+    /// Trees which lack the path.
+    pub absent: Vec<usize>,
+    /// Trees containing the path, grouped by identical content. All
+    /// directories form one class.
+    pub classes: Vec<Vec<usize>>,
+    /// Trees where the path is neither a file nor a directory, or could
+    /// not be compared.
+    pub funny: Vec<usize>,
+}
+
+impl MultiEntry {
+    /// Whether the path is the same in all trees.
+    pub fn is_same(&self) -> bool {
+        self.absent.is_empty() && self.funny.is_empty() && self.classes.len() <= 1
+    }
+}
+
+impl MultiCmp {
+    /// Compare the directories `roots` with the default ignore and hide
+    /// lists and shallow file comparison.
+    pub fn new<I, P>(roots: I) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        MultiCmpBuilder::new(roots).build()
+    }
+
+    /// The paths which are not the same in all trees.
+    pub fn differences(&self) -> impl Iterator<Item = (&PathBuf, &MultiEntry)> {
+        self.entries.iter().filter(|(_, entry)| !entry.is_same())
+    }
+
+    /// Print the paths which differ, with the trees grouped by content.
+    pub fn report(&self) {
+        print!("{}", self);
+    }
+}
+
+/// Lists the roots, then every differing path with its classes of trees.
+impl fmt::Display for MultiCmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let roots = |trees: &[usize]| -> Vec<PathBuf> {
+            trees.iter().map(|&i| self.roots[i].clone()).collect()
+        };
+        writeln!(f, "Roots : {}", PyList(&self.roots))?;
+        for (path, entry) in self.differences() {
+            write!(f, "{} :", path.display())?;
+            for class in &entry.classes {
+                write!(f, " {}", PyList(&roots(class)))?;
+            }
+            if !entry.absent.is_empty() {
+                write!(f, " missing in {}", PyList(&roots(&entry.absent)))?;
+            }
+            if !entry.funny.is_empty() {
+                write!(f, " trouble in {}", PyList(&roots(&entry.funny)))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builder of a `MultiCmp`, with the settings of `DirCmpBuilder`.
+#[derive(Debug, Clone)]
+pub struct MultiCmpBuilder {
+    roots: Vec<PathBuf>,
+    ignore: Vec<OsString>,
+    hide: Vec<OsString>,
+    options: CmpOptions,
+}
+
+impl MultiCmpBuilder {
+    /// Create a builder comparing the directories `roots`.
+    pub fn new<I, P>(roots: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        MultiCmpBuilder {
+            roots: roots
+                .into_iter()
+                .map(|root| root.as_ref().to_path_buf())
+                .collect(),
+            ignore: DEFAULT_IGNORES.iter().map(OsString::from).collect(),
+            hide: DEFAULT_HIDES.iter().map(OsString::from).collect(),
+            options: CmpOptions::new(),
+        }
+    }
+
+    /// Names to ignore, replacing DEFAULT_IGNORES.
+    pub fn ignore<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.ignore = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Names to hide, replacing DEFAULT_HIDES.
+    pub fn hide<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.hide = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Compare files by stat() signature only (the default), or by content.
+    pub fn shallow(mut self, shallow: bool) -> Self {
+        self.options = self.options.shallow(shallow);
+        self
+    }
+
+    /// Replace all file comparison options at once.
+    pub fn cmp_options(mut self, options: CmpOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Walk all directories and build the comparison.
+    pub fn build(&self) -> io::Result<MultiCmp> {
+        let mut x = MultiCmp {
+            roots: self.roots.clone(),
+            entries: BTreeMap::new(),
+        };
+        let all: Vec<usize> = (0..self.roots.len()).collect();
+        self.build_at(Path::new(""), &all, &mut x)?;
+        Ok(x)
+    }
+
+    /// Compare the entries of directory `rel`, found in the trees `dirs`.
+    fn build_at(&self, rel: &Path, dirs: &[usize], x: &mut MultiCmp) -> io::Result<()> {
+        let mut lists = BTreeMap::new();
+        for &i in dirs {
+            let names = RealFs.read_dir(&self.roots[i].join(rel))?;
+            lists.insert(i, filter_names(names, &self.hide, &self.ignore));
+        }
+        let names: BTreeSet<&PathBuf> = lists.values().flatten().collect();
+
+        for name in names {
+            let path = rel.join(name);
+            let mut entry = MultiEntry::default();
+            let (mut subdirs, mut files) = (Vec::new(), Vec::new());
+            for i in 0..self.roots.len() {
+                let listed = lists.get(&i).map(|list| list.binary_search(name));
+                if !matches!(listed, Some(Ok(_))) {
+                    entry.absent.push(i);
+                    continue;
+                }
+                entry.present.push(i);
+                match os::stat(self.roots[i].join(&path), FOLLOW_SYMLINKS_DEFAULT) {
+                    Ok(st) if stat::S_ISDIR(st.st_mode) => subdirs.push(i),
+                    Ok(st) if stat::S_ISREG(st.st_mode) => files.push(i),
+                    _ => entry.funny.push(i),
+                }
+            }
+
+            if !subdirs.is_empty() {
+                entry.classes.push(subdirs.clone());
+            }
+            let dir_classes = entry.classes.len();
+            for i in files {
+                self.classify(&path, i, dir_classes, &mut entry);
+            }
+            x.entries.insert(path.clone(), entry);
+            if !subdirs.is_empty() {
+                self.build_at(&path, &subdirs, x)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Put the file `path` of tree `i` into the class of the first
+    /// identical file, or into a new class. The first `dir_classes`
+    /// classes hold directories.
+    fn classify(&self, path: &Path, i: usize, dir_classes: usize, entry: &mut MultiEntry) {
+        let file = self.roots[i].join(path);
+        for class in entry.classes.iter_mut().skip(dir_classes) {
+            let other = self.roots[class[0]].join(path);
+            match cmp_with(&other, &file, &self.options) {
+                Ok(true) => {
+                    class.push(i);
+                    return;
+                }
+                Ok(false) => {}
+                Err(_) => {
+                    entry.funny.push(i);
+                    return;
+                }
+            }
+        }
+        entry.classes.push(vec![i]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_multi() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_multi");
+        let _ = fs::remove_dir_all(&test_dir);
+        let hosts: Vec<PathBuf> = (0..5)
+            .map(|i| test_dir.join(format!("host{}", i)))
+            .collect();
+        for (i, host) in hosts.iter().enumerate() {
+            fs::create_dir_all(host.join("lib")).unwrap();
+            fs::write(host.join("README"), "same").unwrap();
+            let build = if i % 2 == 0 { "even" } else { "odd" };
+            fs::write(host.join("lib").join("build.o"), build).unwrap();
+        }
+        fs::write(hosts[4].join("extra.log"), "log").unwrap();
+        fs::remove_file(hosts[3].join("README")).unwrap();
+        fs::create_dir(hosts[2].join("README.d")).unwrap();
+        fs::create_dir(hosts[1].join("README.d")).unwrap();
+
+        let x = MultiCmpBuilder::new(&hosts).shallow(false).build().unwrap();
+        let entry = &x.entries[Path::new("README")];
+        assert_eq!(entry.present, vec![0, 1, 2, 4]);
+        assert_eq!(entry.absent, vec![3]);
+        assert_eq!(entry.classes, vec![vec![0, 1, 2, 4]]);
+        let entry = &x.entries[Path::new("lib/build.o")];
+        assert_eq!(entry.classes, vec![vec![0, 2, 4], vec![1, 3]]);
+        assert!(x.entries[Path::new("lib")].is_same());
+        assert_eq!(x.entries[Path::new("README.d")].classes, vec![vec![1, 2]]);
+        assert_eq!(x.entries[Path::new("extra.log")].present, vec![4]);
+
+        let differing: Vec<&PathBuf> = x.differences().map(|(path, _)| path).collect();
+        assert_eq!(
+            differing,
+            vec![
+                Path::new("README"),
+                Path::new("README.d"),
+                Path::new("extra.log"),
+                Path::new("lib/build.o")
+            ]
+        );
+        let report = x.to_string();
+        assert!(report.contains(&format!(
+            "lib/build.o : ['{}', '{}', '{}'] ['{}', '{}']",
+            hosts[0].display(),
+            hosts[2].display(),
+            hosts[4].display(),
+            hosts[1].display(),
+            hosts[3].display()
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_multi_funny() {
+        use std::os::unix::fs::symlink;
+
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_multi_funny");
+        let _ = fs::remove_dir_all(&test_dir);
+        let hosts: Vec<PathBuf> = (0..4)
+            .map(|i| test_dir.join(format!("host{}", i)))
+            .collect();
+        for host in &hosts {
+            fs::create_dir_all(host).unwrap();
+        }
+        // a file, the same file, a dangling link and a directory
+        fs::write(hosts[0].join("conf"), "same").unwrap();
+        fs::write(hosts[1].join("conf"), "same").unwrap();
+        symlink("missing", hosts[2].join("conf")).unwrap();
+        fs::create_dir(hosts[3].join("conf")).unwrap();
+        // a link to /dev/null is a character device
+        for host in &hosts[..2] {
+            symlink("/dev/null", host.join("null")).unwrap();
+        }
+
+        let x = MultiCmpBuilder::new(&hosts).shallow(false).build().unwrap();
+        let entry = &x.entries[Path::new("conf")];
+        assert_eq!(entry.present, vec![0, 1, 2, 3]);
+        assert_eq!(entry.funny, vec![2]);
+        assert_eq!(entry.classes, vec![vec![3], vec![0, 1]]);
+        assert!(!entry.is_same());
+        let entry = &x.entries[Path::new("null")];
+        assert_eq!(entry.funny, vec![0, 1]);
+        assert!(entry.classes.is_empty());
+        assert!(!entry.is_same());
+
+        let report = x.to_string();
+        assert!(report.contains(&format!(
+            "conf : ['{}'] ['{}', '{}'] trouble in ['{}']\n",
+            hosts[3].display(),
+            hosts[0].display(),
+            hosts[1].display(),
+            hosts[2].display()
+        )));
+        assert!(report.contains(&format!(
+            "null : missing in ['{}', '{}'] trouble in ['{}', '{}']\n",
+            hosts[2].display(),
+            hosts[3].display(),
+            hosts[0].display(),
+            hosts[1].display()
+        )));
+    }
+}
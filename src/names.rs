@@ -0,0 +1,194 @@
+//! Pairing of names which differ between the two trees of a `DirCmp` only
+//! by case or Unicode normalization.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::DirCmp;
+
+/// How the names of both directories are paired, see
+/// `DirCmpBuilder::name_matching()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameMatching {
+    /// Names must be equal byte for byte (the default).
+    #[default]
+    Exact,
+    /// Names equal but for case are paired, like on FAT, exFAT and the
+    /// default macOS file systems.
+    CaseInsensitive,
+    /// Names equal after Unicode normalization are paired, as macOS
+    /// stores names decomposed (NFD) where other systems keep them
+    /// composed (NFC).
+    Normalized,
+    /// Both `CaseInsensitive` and `Normalized`.
+    CaseInsensitiveNormalized,
+}
+
+impl NameMatching {
+    /// Names of the modes, as accepted by `from_str()`.
+    pub const NAMES: [&'static str; 4] = ["exact", "case", "unicode", "case-unicode"];
+
+    /// The key under which a name is paired, or None for names which are
+    /// not valid Unicode and only match exactly.
+    fn key(&self, name: &Path) -> Option<String> {
+        let name = name.to_str()?;
+        Some(match self {
+            NameMatching::Exact => name.to_string(),
+            NameMatching::CaseInsensitive => name.to_lowercase(),
+            NameMatching::Normalized => name.nfc().collect(),
+            NameMatching::CaseInsensitiveNormalized => name
+                .nfd()
+                .collect::<String>()
+                .to_lowercase()
+                .nfc()
+                .collect(),
+        })
+    }
+}
+
+impl FromStr for NameMatching {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exact" => Ok(NameMatching::Exact),
+            "case" => Ok(NameMatching::CaseInsensitive),
+            "unicode" => Ok(NameMatching::Normalized),
+            "case-unicode" => Ok(NameMatching::CaseInsensitiveNormalized),
+            _ => Err(format!(
+                "unknown name matching '{}', expected one of: {}",
+                s,
+                NameMatching::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl DirCmp {
+    /// The name of a common entry in the right directory, which differs
+    /// from `x` when paired by `DirCmpBuilder::name_matching()`.
+    pub fn right_name<'a>(&'a self, x: &'a Path) -> &'a Path {
+        self.name_pairs.get(x).map_or(x, PathBuf::as_path)
+    }
+
+    /// Pair the names of `left_only` and `right_only` with the same key,
+    /// moving them to `common`. Names sharing a key with another name of
+    /// the same directory are listed in `name_collisions` and left alone.
+    pub(crate) fn match_names(&mut self, matching: NameMatching) {
+        if matching == NameMatching::Exact {
+            return;
+        }
+        let group = |names: &[PathBuf]| {
+            let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+            for x in names {
+                if let Some(key) = matching.key(x) {
+                    groups.entry(key).or_default().push(x.clone());
+                }
+            }
+            groups
+        };
+        let left_groups = group(&self.left_list);
+        let right_groups = group(&self.right_list);
+
+        for groups in [&left_groups, &right_groups] {
+            for names in groups.values().filter(|names| names.len() > 1) {
+                self.name_collisions.extend(names.iter().cloned());
+            }
+        }
+        self.name_collisions.sort();
+        self.name_collisions.dedup();
+
+        for (key, left) in &left_groups {
+            let right = match right_groups.get(key) {
+                Some(right) => right,
+                None => continue,
+            };
+            if let ([x], [y]) = (&left[..], &right[..]) {
+                if x != y {
+                    self.left_only.retain(|name| name != x);
+                    self.right_only.retain(|name| name != y);
+                    self.common.push(x.clone());
+                    self.name_pairs.insert(x.clone(), y.clone());
+                }
+            }
+        }
+        self.common.sort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemFs;
+    use crate::DirCmpBuilder;
+
+    #[test]
+    fn test_name_matching() {
+        let mut left = MemFs::new("left");
+        left.add_file("README.md", "readme");
+        left.add_file("caf\u{e9}.txt", "nfc");
+        left.add_file("Makefile", "all:");
+        left.add_file("makefile", "all:");
+        left.add_dir("Docs");
+        left.add_file("Docs/Guide.txt", "left");
+        let mut right = MemFs::new("right");
+        right.add_file("readme.md", "readme");
+        right.add_file("cafe\u{301}.txt", "nfd");
+        right.add_file("MAKEFILE", "all:");
+        right.add_dir("docs");
+        right.add_file("docs/guide.txt", "right");
+
+        let build = |matching| {
+            DirCmpBuilder::new("left", "right")
+                .left_fs(left.clone())
+                .right_fs(right.clone())
+                .shallow(false)
+                .name_matching(matching)
+                .build()
+                .unwrap()
+        };
+
+        let x = build(NameMatching::Exact);
+        assert!(x.common.is_empty());
+        assert!(x.name_pairs.is_empty() && x.name_collisions.is_empty());
+
+        let x = build(NameMatching::Normalized);
+        assert_eq!(x.common, vec![PathBuf::from("caf\u{e9}.txt")]);
+        assert_eq!(
+            x.right_name(Path::new("caf\u{e9}.txt")),
+            Path::new("cafe\u{301}.txt")
+        );
+        assert_eq!(x.diff_files, vec![PathBuf::from("caf\u{e9}.txt")]);
+
+        let x = build(NameMatching::CaseInsensitive);
+        assert_eq!(x.same_files, vec![PathBuf::from("README.md")]);
+        assert_eq!(x.subdirs[Path::new("Docs")].right, Path::new("right/docs"));
+        assert_eq!(
+            x.subdirs[Path::new("Docs")].diff_files,
+            vec![PathBuf::from("Guide.txt")]
+        );
+        assert_eq!(
+            x.name_collisions,
+            vec![PathBuf::from("Makefile"), PathBuf::from("makefile")]
+        );
+        assert_eq!(
+            x.left_only,
+            vec![
+                PathBuf::from("Makefile"),
+                PathBuf::from("caf\u{e9}.txt"),
+                PathBuf::from("makefile"),
+            ]
+        );
+        assert_eq!(x.right_only.len(), 2);
+
+        let x = build(NameMatching::CaseInsensitiveNormalized);
+        assert_eq!(x.common.len(), 3);
+        assert_eq!(
+            "case-unicode".parse(),
+            Ok(NameMatching::CaseInsensitiveNormalized)
+        );
+    }
+}
@@ -22,23 +22,24 @@
 
 use std::fs::{self};
 use std::io::{self};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 #[cfg(windows)]
-pub use nt::stat;
+pub use nt::from_metadata;
 
 #[cfg(unix)]
-pub use posix::stat;
+pub use posix::from_metadata;
 
+/// Result of os.stat(), with times in seconds since the Unix epoch.
 #[derive(Debug)]
 pub struct StatResult {
     pub st_mode: u32,
-    st_ino: u64,
-    st_dev: u64,
-    st_nlink: u64,
-    st_uid: u32,
-    st_gid: u32,
+    pub st_ino: u64,
+    pub st_dev: u64,
+    pub st_nlink: u64,
+    pub st_uid: u32,
+    pub st_gid: u32,
     pub st_size: u64,
     pub st_atime: f64,
     pub st_mtime: f64,
@@ -49,11 +50,9 @@ pub struct StatResult {
 mod nt {
     use super::*;
 
-    pub fn stat(path: impl AsRef<Path>, follow_symlinks: bool) -> io::Result<StatResult> {
+    pub fn from_metadata(meta: &fs::Metadata) -> io::Result<StatResult> {
         use std::os::windows::fs::MetadataExt;
 
-        let meta = fs_metadata(path, follow_symlinks)?;
-
         // // When use #![feature(windows_by_handle)] in nightly
         // let st_ino = meta.file_index().unwrap();
         // let st_dev = meta.volume_serial_number().unwrap() as u64;
@@ -102,7 +101,7 @@ mod posix {
     use super::*;
     use std::time::Duration;
 
-    pub fn stat(path: impl AsRef<Path>, follow_symlinks: bool) -> io::Result<StatResult> {
+    pub fn from_metadata(meta: &fs::Metadata) -> io::Result<StatResult> {
         #[cfg(target_os = "android")]
         use std::os::android::fs::MetadataExt;
         #[cfg(target_os = "linux")]
@@ -114,8 +113,6 @@ mod posix {
         #[cfg(target_os = "redox")]
         use std::os::redox::fs::MetadataExt;
 
-        let meta = fs_metadata(path, follow_symlinks)?;
-
         Ok(StatResult {
             st_mode: meta.st_mode(),
             st_ino: meta.st_ino(),
@@ -136,6 +133,11 @@ mod posix {
     }
 }
 
+/// Perform a stat system call on the given path.
+pub fn stat(path: impl AsRef<Path>, follow_symlinks: bool) -> io::Result<StatResult> {
+    from_metadata(&fs_metadata(path, follow_symlinks)?)
+}
+
 fn fs_metadata(path: impl AsRef<Path>, follow_symlinks: bool) -> io::Result<fs::Metadata> {
     if follow_symlinks {
         fs::metadata(path.as_ref())
@@ -144,6 +146,37 @@ fn fs_metadata(path: impl AsRef<Path>, follow_symlinks: bool) -> io::Result<fs::
     }
 }
 
+/// Encode a path to bytes, like os.fsencode().
+#[cfg(unix)]
+pub fn fsencode(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Encode a path to bytes, like os.fsencode(), replacing what is not
+/// valid Unicode.
+#[cfg(not(unix))]
+pub fn fsencode(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Decode bytes to a path, like os.fsdecode().
+#[cfg(unix)]
+pub fn fsdecode(bytes: Vec<u8>) -> io::Result<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    Ok(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// Decode bytes to a path, like os.fsdecode(), failing on bytes which
+/// are not UTF-8.
+#[cfg(not(unix))]
+pub fn fsdecode(bytes: Vec<u8>) -> io::Result<PathBuf> {
+    String::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "path is not valid UTF-8"))
+}
+
 fn to_seconds_from_unix_epoch(sys_time: SystemTime) -> f64 {
     match sys_time.duration_since(SystemTime::UNIX_EPOCH) {
         Ok(duration) => duration.as_secs_f64(),
@@ -159,6 +192,7 @@ mod tests {
     use std::io::Write;
 
     #[test]
+    #[allow(clippy::unused_io_amount)]
     fn test_stat() {
         let temp_dir = env::temp_dir();
         let test_dir = dbg!(temp_dir.join("test_filecmp").join("test_stat"));
@@ -0,0 +1,430 @@
+//! Detection of files moved or renamed between the two trees of a
+//! `DirCmp`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::{stat, DirCmp, DirCmpBuilder, Source, FOLLOW_SYMLINKS_DEFAULT};
+
+/// Files larger than this are only matched by identical content.
+const MAX_SIMILARITY_SIZE: u64 = 8 << 20;
+
+/// Similar files are not looked for when either tree has more unmatched
+/// files than this, as every pair would be compared.
+const MAX_SIMILARITY_CANDIDATES: usize = 1000;
+
+/// A file found at a different path in each tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Move {
+    /// Path in the left tree, relative to its root.
+    pub from: PathBuf,
+    /// Path in the right tree, relative to its root.
+    pub to: PathBuf,
+    /// Share of lines both files have in common, 1.0 for identical files.
+    pub similarity: f64,
+}
+
+/// A file only found in one tree.
+struct Candidate {
+    /// Path relative to the root.
+    rel: PathBuf,
+    /// Whether it is listed in `left_only` or `right_only` itself, rather
+    /// than inside a directory listed there.
+    listed: bool,
+    size: u64,
+}
+
+impl DirCmpBuilder {
+    /// Match the files only found in one tree with files only found in the
+    /// other, and record them as moves.
+    pub(crate) fn detect_moves(
+        &self,
+        dd: &mut DirCmp,
+        left_src: &Source,
+        right_src: &Source,
+        threshold: f64,
+    ) -> io::Result<()> {
+        let mut lefts = Vec::new();
+        let mut rights = Vec::new();
+        collect(
+            dd,
+            Path::new(""),
+            Path::new(""),
+            &mut |dd, rel, right_rel| {
+                for x in &dd.left_only {
+                    self.candidates(left_src, &dd.left.join(x), &rel.join(x), true, &mut lefts)?;
+                }
+                for x in &dd.right_only {
+                    self.candidates(
+                        right_src,
+                        &dd.right.join(x),
+                        &right_rel.join(x),
+                        true,
+                        &mut rights,
+                    )?;
+                }
+                Ok(())
+            },
+        )?;
+
+        let mut matched_left = vec![false; lefts.len()];
+        let mut matched_right = vec![false; rights.len()];
+        let mut moves = Vec::new();
+
+        // identical content first, among files of the same size and digest
+        let sizes = |candidates: &[Candidate]| -> HashSet<u64> {
+            candidates.iter().map(|c| c.size).collect()
+        };
+        let (left_sizes, right_sizes) = (sizes(&lefts), sizes(&rights));
+        let cache = self.options.digest_cache;
+        let mut by_digest: HashMap<String, VecDeque<usize>> = HashMap::new();
+        for (j, right) in rights.iter().enumerate() {
+            if !left_sizes.contains(&right.size) {
+                continue;
+            }
+            if let Ok(digest) = right_src.sha256(&dd.right.join(&right.rel), cache) {
+                by_digest.entry(digest).or_default().push_back(j);
+            }
+        }
+        for (i, left) in lefts.iter().enumerate() {
+            if !right_sizes.contains(&left.size) {
+                continue;
+            }
+            let j = match left_src
+                .sha256(&dd.left.join(&left.rel), cache)
+                .ok()
+                .and_then(|digest| by_digest.get_mut(&digest)?.pop_front())
+            {
+                Some(j) => j,
+                None => continue,
+            };
+            matched_left[i] = true;
+            matched_right[j] = true;
+            moves.push((i, j, 1.0));
+        }
+
+        // then the most similar pairs above the threshold, among files
+        // whose sizes do not already rule it out
+        let unmatched = |candidates: &[Candidate], matched: &[bool]| -> Vec<usize> {
+            (0..candidates.len())
+                .filter(|&i| !matched[i] && candidates[i].size <= MAX_SIMILARITY_SIZE)
+                .collect()
+        };
+        let unmatched_left = unmatched(&lefts, &matched_left);
+        let unmatched_right = unmatched(&rights, &matched_right);
+        if threshold < 1.0
+            && unmatched_left.len() <= MAX_SIMILARITY_CANDIDATES
+            && unmatched_right.len() <= MAX_SIMILARITY_CANDIDATES
+        {
+            let lines =
+                |src: &Source, root: &Path, c: &Candidate| -> Option<HashMap<Vec<u8>, usize>> {
+                    let mut data = Vec::new();
+                    src.fs()
+                        .open(&root.join(&c.rel))
+                        .ok()?
+                        .read_to_end(&mut data)
+                        .ok()?;
+                    Some(line_counts(&data))
+                };
+            // line counts are loaded on first use
+            let mut left_lines: Vec<Option<Option<_>>> = lefts.iter().map(|_| None).collect();
+            let mut right_lines: Vec<Option<Option<_>>> = rights.iter().map(|_| None).collect();
+            let mut pairs = Vec::new();
+            for &i in &unmatched_left {
+                for &j in &unmatched_right {
+                    let (a, b) = (lefts[i].size, rights[j].size);
+                    if 2.0 * (a.min(b) as f64) < threshold * (a + b) as f64 {
+                        continue;
+                    }
+                    let l =
+                        left_lines[i].get_or_insert_with(|| lines(left_src, &dd.left, &lefts[i]));
+                    let r = right_lines[j]
+                        .get_or_insert_with(|| lines(right_src, &dd.right, &rights[j]));
+                    if let (Some(l), Some(r)) = (l, r) {
+                        let score = similarity(l, r);
+                        if score >= threshold {
+                            pairs.push((i, j, score));
+                        }
+                    }
+                }
+            }
+            pairs.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+            for (i, j, score) in pairs {
+                if !matched_left[i] && !matched_right[j] {
+                    matched_left[i] = true;
+                    matched_right[j] = true;
+                    moves.push((i, j, score));
+                }
+            }
+        }
+
+        moves.sort_by(|a, b| lefts[a.0].rel.cmp(&lefts[b.0].rel));
+        for (i, j, similarity) in moves {
+            let (left, right) = (&lefts[i], &rights[j]);
+            if left.listed {
+                unlist(dd, &left.rel, false, |dd| &mut dd.left_only);
+            }
+            if right.listed {
+                unlist(dd, &right.rel, true, |dd| &mut dd.right_only);
+            }
+            dd.moves.push(Move {
+                from: left.rel.clone(),
+                to: right.rel.clone(),
+                similarity,
+            });
+        }
+        Ok(())
+    }
+
+    /// Add the regular files at `path`, or below it if it is a directory.
+    fn candidates(
+        &self,
+        src: &Source,
+        path: &Path,
+        rel: &Path,
+        listed: bool,
+        out: &mut Vec<Candidate>,
+    ) -> io::Result<()> {
+        let st = match src.fs().stat(path, FOLLOW_SYMLINKS_DEFAULT) {
+            Ok(st) => st,
+            Err(_) => return Ok(()),
+        };
+        if stat::S_ISREG(st.st_mode) {
+            // empty files would all match each other
+            if st.st_size == 0 {
+                return Ok(());
+            }
+            out.push(Candidate {
+                rel: rel.to_path_buf(),
+                listed,
+                size: st.st_size,
+            });
+        } else if stat::S_ISDIR(st.st_mode) {
+            for name in self.filter(src.fs().read_dir(path)?) {
+                self.candidates(src, &path.join(&name), &rel.join(&name), false, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Call `f` on a comparison and all its subdirectories, with their paths
+/// relative to the left and the right root, which differ below paired
+/// names.
+fn collect(
+    dd: &DirCmp,
+    rel: &Path,
+    right_rel: &Path,
+    f: &mut dyn FnMut(&DirCmp, &Path, &Path) -> io::Result<()>,
+) -> io::Result<()> {
+    f(dd, rel, right_rel)?;
+    for (x, sd) in &dd.subdirs {
+        collect(sd, &rel.join(x), &right_rel.join(dd.right_name(x)), f)?;
+    }
+    Ok(())
+}
+
+/// Remove a moved file from the list of its parent directory, given its
+/// path in the right tree if `right`.
+fn unlist(dd: &mut DirCmp, rel: &Path, right: bool, list: fn(&mut DirCmp) -> &mut Vec<PathBuf>) {
+    let mut parent = dd;
+    for name in rel.parent().into_iter().flat_map(Path::iter) {
+        // subdirs are keyed by their left names
+        let key = match parent
+            .name_pairs
+            .iter()
+            .find(|(_, y)| right && y.as_os_str() == name)
+        {
+            Some((x, _)) => x.clone(),
+            None => PathBuf::from(name),
+        };
+        parent = match parent.subdirs.get_mut(&key) {
+            Some(sd) => sd,
+            None => return,
+        };
+    }
+    if let Some(name) = rel.file_name() {
+        list(parent).retain(|x| x.as_os_str() != name);
+    }
+}
+
+/// Count the lines of a file, each with its line ending.
+fn line_counts(data: &[u8]) -> HashMap<Vec<u8>, usize> {
+    let mut counts = HashMap::new();
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        *counts.entry(line.to_vec()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Share of lines two files have in common, from 0.0 to 1.0.
+fn similarity(l: &HashMap<Vec<u8>, usize>, r: &HashMap<Vec<u8>, usize>) -> f64 {
+    let total: usize = l.values().sum::<usize>() + r.values().sum::<usize>();
+    if total == 0 {
+        return 1.0;
+    }
+    let common: usize = l
+        .iter()
+        .map(|(line, n)| (*n).min(r.get(line).copied().unwrap_or(0)))
+        .sum();
+    2.0 * common as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vfs::MemFs;
+    use crate::{DirCmpBuilder, NameMatching};
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_detect_moves() {
+        let body: String = (0..20).map(|i| format!("line {}\n", i)).collect();
+        let mut left = MemFs::new("left");
+        left.add_file("src/old_name.rs", "fn main() {}\n");
+        left.add_file("docs/guide.md", body.clone());
+        left.add_file("gone.txt", "unrelated\n");
+        left.add_file("assets/logo.svg", "<svg/>");
+        let mut right = MemFs::new("right");
+        right.add_file("src/new_name.rs", "fn main() {}\n");
+        right.add_file("guide.md", format!("{}line 20\n", body));
+        right.add_file("added.txt", "something else\n");
+        right.add_file("static/logo.svg", "<svg/>");
+        right.add_dir("docs");
+        right.add_dir("src");
+
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left.clone())
+            .right_fs(right.clone())
+            .shallow(false)
+            .renames(true)
+            .build()
+            .unwrap();
+        let moves: Vec<(&Path, &Path)> = x
+            .moves
+            .iter()
+            .map(|m| (m.from.as_path(), m.to.as_path()))
+            .collect();
+        assert_eq!(
+            moves,
+            vec![
+                (Path::new("assets/logo.svg"), Path::new("static/logo.svg")),
+                (Path::new("src/old_name.rs"), Path::new("src/new_name.rs")),
+            ]
+        );
+        assert!(x.subdirs[Path::new("src")].left_only.is_empty());
+        assert!(x.subdirs[Path::new("src")].right_only.is_empty());
+        assert_eq!(
+            x.left_only,
+            vec![PathBuf::from("assets"), "gone.txt".into()]
+        );
+
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .shallow(false)
+            .rename_similarity(0.9)
+            .build()
+            .unwrap();
+        assert_eq!(x.moves.len(), 3);
+        let guide = &x.moves[1];
+        assert_eq!(guide.from, Path::new("docs/guide.md"));
+        assert_eq!(guide.to, Path::new("guide.md"));
+        assert!(guide.similarity > 0.9 && guide.similarity < 1.0);
+        assert_eq!(
+            x.left_only,
+            vec![PathBuf::from("assets"), "gone.txt".into()]
+        );
+        assert_eq!(
+            x.right_only,
+            vec![PathBuf::from("added.txt"), "static".into()]
+        );
+    }
+
+    #[test]
+    fn test_detect_moves_name_pairs() {
+        let mut left = MemFs::new("left");
+        left.add_file("Sub/old.txt", "moved\n");
+        let mut right = MemFs::new("right");
+        right.add_file("sub/new.txt", "moved\n");
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .renames(true)
+            .name_matching(NameMatching::CaseInsensitive)
+            .build()
+            .unwrap();
+        assert_eq!(x.moves.len(), 1);
+        assert_eq!(x.moves[0].from, Path::new("Sub/old.txt"));
+        assert_eq!(x.moves[0].to, Path::new("sub/new.txt"));
+        assert!(x.subdirs[Path::new("Sub")].left_only.is_empty());
+        assert!(x.subdirs[Path::new("Sub")].right_only.is_empty());
+    }
+
+    #[test]
+    fn test_detect_moves_edge_cases() {
+        let mut left = MemFs::new("left");
+        left.add_file("empty", "");
+        left.add_file("a.txt", "same\n");
+        left.add_file("b.txt", "same\n");
+        let mut right = MemFs::new("right");
+        right.add_file("also_empty", "");
+        right.add_file("c.txt", "same\n");
+        let build = |threshold| {
+            DirCmpBuilder::new("left", "right")
+                .left_fs(left.clone())
+                .right_fs(right.clone())
+                .rename_similarity(threshold)
+                .build()
+        };
+
+        // empty files are not moves, and copies of one file match once
+        let x = build(0.5).unwrap();
+        assert_eq!(x.moves.len(), 1);
+        assert_eq!(x.moves[0].from, Path::new("a.txt"));
+        assert_eq!(x.moves[0].to, Path::new("c.txt"));
+        assert_eq!(x.left_only, vec![PathBuf::from("b.txt"), "empty".into()]);
+        assert_eq!(x.right_only, vec![PathBuf::from("also_empty")]);
+
+        for threshold in [-0.1, 0.0, 1.5, f64::NAN] {
+            let err = build(threshold).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_detect_moves_limits() {
+        // sizes too far apart for the threshold, whatever the lines
+        let mut left = MemFs::new("left");
+        left.add_file("short.txt", "line\n");
+        let mut right = MemFs::new("right");
+        right.add_file("long.txt", format!("line\n{}\n", "x".repeat(100)));
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .rename_similarity(0.5)
+            .build()
+            .unwrap();
+        assert!(x.moves.is_empty());
+
+        // too many candidates to compare every pair, but identical files
+        // are still matched
+        let mut left = MemFs::new("left");
+        let mut right = MemFs::new("right");
+        for i in 0..=super::MAX_SIMILARITY_CANDIDATES {
+            left.add_file(format!("l{}", i), format!("common\nleft {}\n", i));
+            right.add_file(format!("r{}", i), format!("common\nright {}\n", i));
+        }
+        left.add_file("same", "same\n");
+        right.add_file("moved", "same\n");
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .rename_similarity(0.5)
+            .build()
+            .unwrap();
+        assert_eq!(x.moves.len(), 1);
+        assert_eq!(x.moves[0].to, Path::new("moved"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Sparse-file aware comparison on Linux, using SEEK_DATA and SEEK_HOLE.
+//!
+//! Holes read as zeros, so ranges which are holes in both files are equal
+//! without reading them. Only data extents, and ranges which are a hole in
+//! one file but data in the other, are read and compared.
+
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::os::unix::io::AsRawFd;
+
+use crate::BUFSIZE;
+
+/// Compare two files by their data extents.
+///
+/// Returns None when neither file has holes, or when the file system does
+/// not support SEEK_DATA, so that the caller falls back to plain reading
+/// from the start of both files.
+pub fn cmp(f1: &File, f2: &File) -> io::Result<Option<bool>> {
+    let (m1, m2) = (f1.metadata()?, f2.metadata()?);
+    let size = m1.len();
+    if size != m2.len() || !(is_sparse(&m1) || is_sparse(&m2)) {
+        return Ok(None);
+    }
+    let outcome = cmp_extents(f1, f2, size);
+    if let Ok(None) = outcome {
+        for file in &mut [f1, f2] {
+            file.seek(SeekFrom::Start(0))?;
+        }
+    }
+    outcome
+}
+
+fn cmp_extents(f1: &File, f2: &File, size: u64) -> io::Result<Option<bool>> {
+    let mut pos = 0;
+    while pos < size {
+        let (d1, d2) = match (
+            seek(f1, pos, libc::SEEK_DATA, size)?,
+            seek(f2, pos, libc::SEEK_DATA, size)?,
+        ) {
+            (Some(d1), Some(d2)) => (d1, d2),
+            _ => return Ok(None),
+        };
+        if d1 > pos && d2 > pos {
+            // holes in both files up to the next data extent
+            pos = d1.min(d2);
+            continue;
+        }
+        let e1 = if d1 == pos {
+            seek(f1, pos, libc::SEEK_HOLE, size)?
+        } else {
+            Some(d1)
+        };
+        let e2 = if d2 == pos {
+            seek(f2, pos, libc::SEEK_HOLE, size)?
+        } else {
+            Some(d2)
+        };
+        let end = match (e1, e2) {
+            (Some(e1), Some(e2)) => e1.min(e2).max(pos + 1),
+            _ => return Ok(None),
+        };
+        if !cmp_range(f1, f2, pos, end)? {
+            return Ok(Some(false));
+        }
+        pos = end;
+    }
+    Ok(Some(true))
+}
+
+/// Whether a file has fewer blocks allocated than its size needs.
+fn is_sparse(meta: &std::fs::Metadata) -> bool {
+    meta.blocks() * 512 < meta.len()
+}
+
+/// Find the next data or hole offset at or after `pos`, or None if the file
+/// system does not support it.
+fn seek(file: &File, pos: u64, whence: libc::c_int, size: u64) -> io::Result<Option<u64>> {
+    let offset = unsafe { libc::lseek(file.as_raw_fd(), pos as libc::off_t, whence) };
+    if offset >= 0 {
+        return Ok(Some((offset as u64).min(size)));
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        // no more data after pos, the rest of the file is a hole
+        Some(libc::ENXIO) => Ok(Some(size)),
+        Some(libc::EINVAL) => Ok(None),
+        _ => Err(err),
+    }
+}
+
+fn cmp_range(f1: &File, f2: &File, start: u64, end: u64) -> io::Result<bool> {
+    let mut buf1: [u8; BUFSIZE] = [0; BUFSIZE];
+    let mut buf2: [u8; BUFSIZE] = [0; BUFSIZE];
+    let mut pos = start;
+    while pos < end {
+        let len = ((end - pos) as usize).min(BUFSIZE);
+        f1.read_exact_at(&mut buf1[..len], pos)?;
+        f2.read_exact_at(&mut buf2[..len], pos)?;
+        if buf1[..len] != buf2[..len] {
+            return Ok(false);
+        }
+        pos += len as u64;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmp_with, CmpOptions};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_cmp_sparse() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_cmp_sparse");
+        fs::create_dir_all(&test_dir).unwrap();
+        let size = 4 << 20;
+        let extents: [(u64, &[u8]); 2] = [(1 << 20, b"first extent"), (3 << 20, b"second extent")];
+
+        let write_sparse = |name: &str, extents: &[(u64, &[u8])]| {
+            let path = test_dir.join(name);
+            let file = File::create(&path).unwrap();
+            file.set_len(size).unwrap();
+            for (offset, data) in extents {
+                file.write_all_at(data, *offset).unwrap();
+            }
+            path
+        };
+        let sparse_path = write_sparse("sparse.img", &extents);
+        let other_path = write_sparse("other.img", &[extents[0], (3 << 20, b"second EXTENT")]);
+        let dense_path = test_dir.join("dense.img");
+        let mut dense = vec![0; size as usize];
+        for (offset, data) in &extents {
+            dense[*offset as usize..*offset as usize + data.len()].copy_from_slice(data);
+        }
+        fs::write(&dense_path, &dense).unwrap();
+
+        let sparse = File::open(&sparse_path).unwrap();
+        let dense = File::open(&dense_path).unwrap();
+        let outcome = cmp(&sparse, &dense).unwrap();
+        assert!(outcome.is_none() || outcome == Some(true));
+
+        let options = CmpOptions::new().shallow(false);
+        assert!(cmp_with(&sparse_path, &dense_path, &options).unwrap());
+        assert!(!cmp_with(&sparse_path, &other_path, &options).unwrap());
+        assert!(!cmp_with(&dense_path, &other_path, &options).unwrap());
+    }
+}
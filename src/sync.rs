@@ -0,0 +1,634 @@
+//! Plans for making a destination tree match a source tree.
+//!
+//! A `SyncPlan` is derived from a `DirCmp` of the source (left) and the
+//! destination (right) directories, both on the real file system. It only
+//! lists operations, so it can be inspected, filtered or serialized (with
+//! the `serde` feature) before anything is changed.
+//!
+//! `SyncPlan::execute()` then applies a plan, and `mirror()` does both in
+//! one go, like `rsync -a`.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File, FileTimes};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::{filter_names, os, stat, DirCmp, DirCmpBuilder, FOLLOW_SYMLINKS_DEFAULT};
+
+/// Compare `src` and `dst`, then make `dst` match `src`.
+///
+/// Files are compared by stat() signature, which is reliable once the
+/// modification times have been copied by a first run. Returns the result
+/// of every operation.
+pub fn mirror(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    options: &SyncOptions,
+) -> io::Result<Vec<OpResult>> {
+    let dd = DirCmpBuilder::new(src, dst).build()?;
+    Ok(SyncPlan::new(&dd)?.execute(options))
+}
+
+/// One step of a `SyncPlan`, with a path relative to both roots.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Op {
+    /// Create a directory missing from the destination.
+    Mkdir(PathBuf),
+    /// Copy a file missing from the destination.
+    Copy(PathBuf),
+    /// Replace a destination file whose content differs.
+    Overwrite(PathBuf),
+    /// Remove a destination file, or a directory emptied by the deletions
+    /// before it.
+    Delete(PathBuf),
+    /// Set the permission bits of an existing destination entry.
+    Chmod(PathBuf, u32),
+}
+
+impl Op {
+    /// The path of the entry, relative to both roots.
+    pub fn path(&self) -> &Path {
+        match self {
+            Op::Mkdir(path)
+            | Op::Copy(path)
+            | Op::Overwrite(path)
+            | Op::Delete(path)
+            | Op::Chmod(path, _) => path,
+        }
+    }
+}
+
+/// Operations making the destination of a `DirCmp` match its source.
+///
+/// Operations come in a safe order:
+///  1. deletions of destination entries whose type differs from the
+///     source, so that the source entry can take their place
+///  2. directory creations, copies and overwrites, parents first
+///  3. permission changes, children first, so that a read-only directory
+///     is only locked once its content is done
+///  4. deletions of entries missing from the source, children first
+///
+/// Copied files and created directories are expected to take the mode of
+/// their source, so they get no `Chmod` of their own. Entries inside
+/// directories missing from one side are listed from the file system, as
+/// `DirCmp` does not walk them; entries which could not be compared are
+/// left alone.
+///
+/// # Example
+///
+/// ```rust
+/// use std::env;
+/// use std::fs;
+/// use std::path::PathBuf;
+/// use filecmp::sync::{Op, SyncPlan};
+/// use filecmp::DirCmpBuilder;
+///
+/// let temp_dir = env::temp_dir().join("filecmp_sync_plan_example");
+/// let src = temp_dir.join("src");
+/// let dst = temp_dir.join("dst");
+/// fs::create_dir_all(&src).unwrap();
+/// fs::create_dir_all(&dst).unwrap();
+/// fs::write(src.join("new.txt"), b"new").unwrap();
+/// fs::write(dst.join("stale.txt"), b"stale").unwrap();
+///
+/// let x = DirCmpBuilder::new(&src, &dst).shallow(false).build().unwrap();
+/// let plan = SyncPlan::new(&x).unwrap();
+/// assert_eq!(
+///     plan.ops,
+///     vec![
+///         Op::Copy(PathBuf::from("new.txt")),
+///         Op::Delete(PathBuf::from("stale.txt")),
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyncPlan {
+    /// Root of the source tree.
+    pub source: PathBuf,
+    /// Root of the destination tree.
+    pub destination: PathBuf,
+    /// Operations in the order they should be applied.
+    pub ops: Vec<Op>,
+}
+
+impl SyncPlan {
+    /// Derive the plan from a comparison of the source (left) and the
+    /// destination (right) directories.
+    ///
+    /// Operations name an entry by the same path in both trees, so a
+    /// comparison pairing names by `DirCmpBuilder::name_matching()` fails
+    /// with `InvalidInput`.
+    pub fn new(dd: &DirCmp) -> io::Result<Self> {
+        if has_name_pairs(dd) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot sync entries whose names differ between both trees",
+            ));
+        }
+        let mut phases = Phases::default();
+        phases.walk(dd, Path::new(""))?;
+        // moved files are not listed as left or right only any more
+        let mut creates: HashSet<Op> = phases.creates.iter().cloned().collect();
+        let mut deletes: HashSet<Op> = phases.deletes.iter().cloned().collect();
+        for m in &dd.moves {
+            let copy = Op::Copy(m.from.clone());
+            if creates.insert(copy.clone()) {
+                phases.creates.push(copy);
+            }
+            let delete = Op::Delete(m.to.clone());
+            if deletes.insert(delete.clone()) {
+                phases.deletes.push(delete);
+            }
+        }
+        let mut ops = phases.conflicts;
+        ops.extend(phases.creates);
+        ops.extend(phases.chmods);
+        ops.extend(phases.deletes);
+        Ok(SyncPlan {
+            source: dd.left.clone(),
+            destination: dd.right.clone(),
+            ops,
+        })
+    }
+
+    /// Whether the destination already matches the source.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Keep only the operations for which `f` returns true, for example to
+    /// leave out deletions.
+    pub fn retain(&mut self, f: impl FnMut(&Op) -> bool) {
+        self.ops.retain(f);
+    }
+
+    /// Apply the operations in order and return the result of each.
+    ///
+    /// Files are copied to a temporary file next to their destination,
+    /// which is then renamed over it, so that readers never see a partial
+    /// file. Copies keep the mode and the access and modification times of
+    /// their source; created directories keep its mode. A failed operation
+    /// does not stop the following ones.
+    pub fn execute(&self, options: &SyncOptions) -> Vec<OpResult> {
+        // deletions making room for an entry of another type always run
+        let created: HashSet<&Path> = self
+            .ops
+            .iter()
+            .filter(|op| matches!(op, Op::Mkdir(_) | Op::Copy(_)))
+            .map(Op::path)
+            .collect();
+        let replaced = |path: &Path| path.ancestors().any(|path| created.contains(path));
+
+        let mut results = Vec::with_capacity(self.ops.len());
+        let mut dir_modes = Vec::new();
+        for (i, op) in self.ops.iter().enumerate() {
+            let status = match op {
+                Op::Delete(path) if !options.delete && !replaced(path) => Status::Skipped,
+                _ if options.dry_run => Status::DryRun,
+                _ => match self.apply(op) {
+                    Ok(dir_mode) => {
+                        dir_modes.extend(dir_mode.map(|mode| (i, mode)));
+                        Status::Done
+                    }
+                    Err(err) => Status::Failed(err),
+                },
+            };
+            results.push(OpResult {
+                op: op.clone(),
+                status,
+            });
+        }
+        // created directories get their final mode once filled, children
+        // first, in case it forbids writing
+        for (i, mode) in dir_modes.into_iter().rev() {
+            if let Err(err) = set_mode(&self.destination.join(results[i].op.path()), mode) {
+                results[i].status = Status::Failed(err);
+            }
+        }
+        results
+    }
+
+    /// Apply one operation. A created directory is left writable, and its
+    /// final mode is returned.
+    fn apply(&self, op: &Op) -> io::Result<Option<u32>> {
+        let src = self.source.join(op.path());
+        let dst = self.destination.join(op.path());
+        match op {
+            Op::Mkdir(_) => {
+                let mode = stat::S_IMODE(os::stat(&src, FOLLOW_SYMLINKS_DEFAULT)?.st_mode);
+                fs::create_dir(&dst)?;
+                set_mode(&dst, mode | 0o700)?;
+                return Ok(Some(mode));
+            }
+            Op::Copy(_) | Op::Overwrite(_) => copy_atomic(&src, &dst)?,
+            Op::Delete(_) => {
+                if fs::symlink_metadata(&dst)?.is_dir() {
+                    fs::remove_dir(&dst)?
+                } else {
+                    fs::remove_file(&dst)?
+                }
+            }
+            Op::Chmod(_, mode) => set_mode(&dst, *mode)?,
+        }
+        Ok(None)
+    }
+}
+
+/// Options for `SyncPlan::execute()` and `mirror()`.
+///
+/// The default applies everything but the deletion of destination entries
+/// missing from the source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncOptions {
+    delete: bool,
+    dry_run: bool,
+}
+
+impl SyncOptions {
+    /// Create the default options.
+    pub fn new() -> Self {
+        SyncOptions::default()
+    }
+
+    /// Delete destination entries missing from the source. Entries of
+    /// another type than their source are replaced either way.
+    pub fn delete(mut self, delete: bool) -> Self {
+        self.delete = delete;
+        self
+    }
+
+    /// Only report the operations which would be applied.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// The result of one operation of `SyncPlan::execute()`, displayed as a
+/// log line such as `copy docs/index.html: done`.
+#[derive(Debug)]
+pub struct OpResult {
+    pub op: Op,
+    pub status: Status,
+}
+
+/// What happened to an operation.
+#[derive(Debug)]
+pub enum Status {
+    Done,
+    /// Not applied, as asked by `SyncOptions::dry_run()`.
+    DryRun,
+    /// Not applied, since `SyncOptions::delete()` is off.
+    Skipped,
+    Failed(io::Error),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Mkdir(path) => write!(f, "mkdir {}", path.display()),
+            Op::Copy(path) => write!(f, "copy {}", path.display()),
+            Op::Overwrite(path) => write!(f, "overwrite {}", path.display()),
+            Op::Delete(path) => write!(f, "delete {}", path.display()),
+            Op::Chmod(path, mode) => write!(f, "chmod {:o} {}", mode, path.display()),
+        }
+    }
+}
+
+impl fmt::Display for OpResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.status {
+            Status::Done => write!(f, "{}: done", self.op),
+            Status::DryRun => write!(f, "{}: dry run", self.op),
+            Status::Skipped => write!(f, "{}: skipped", self.op),
+            Status::Failed(err) => write!(f, "{}: failed: {}", self.op, err),
+        }
+    }
+}
+
+/// Copy a file through a temporary file renamed over the destination,
+/// keeping the mode and times of the source.
+///
+/// The temporary file gets a random name and is created exclusively, so a
+/// link planted in the destination directory is never followed.
+fn copy_atomic(src: &Path, dst: &Path) -> io::Result<()> {
+    let mut source = File::open(src)?;
+    let meta = source.metadata()?;
+    let (tmp, mut file) = create_temporary(dst)?;
+    let outcome = (|| {
+        io::copy(&mut source, &mut file)?;
+        let times = FileTimes::new()
+            .set_accessed(meta.accessed()?)
+            .set_modified(meta.modified()?);
+        file.set_times(times)?;
+        file.set_permissions(meta.permissions())?;
+        drop(file);
+        fs::rename(&tmp, dst)
+    })();
+    if outcome.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    outcome
+}
+
+/// Create a new file with a random name next to `dst`, failing rather than
+/// opening an existing file or following a link.
+fn create_temporary(dst: &Path) -> io::Result<(PathBuf, File)> {
+    let name = dst.file_name().unwrap_or_default().to_string_lossy();
+    let mut last_err = None;
+    for _ in 0..100 {
+        let tmp = dst.with_file_name(format!(".{}.filecmp-{:016x}.tmp", name, random()));
+        match File::options().write(true).create_new(true).open(&tmp) {
+            Ok(file) => return Ok((tmp, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => last_err = Some(err),
+            Err(err) => return Err(err),
+        }
+    }
+    Err(last_err.expect("at least one attempt"))
+}
+
+/// A random number, from the per-process random keys of the standard
+/// library's hash maps.
+fn random() -> u64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    hasher.finish()
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+/// Only the write permission can be set outside Unix.
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+/// Operations collected per phase while walking a `DirCmp`.
+#[derive(Default)]
+struct Phases {
+    conflicts: Vec<Op>,
+    creates: Vec<Op>,
+    chmods: Vec<Op>,
+    deletes: Vec<Op>,
+}
+
+impl Phases {
+    fn walk(&mut self, dd: &DirCmp, rel: &Path) -> io::Result<()> {
+        for x in &dd.left_only {
+            self.create(dd, &dd.left.join(x), &rel.join(x))?;
+        }
+        for x in &dd.common_funny {
+            let a_stat = os::stat(dd.left.join(x), FOLLOW_SYMLINKS_DEFAULT);
+            let b_stat = os::stat(dd.right.join(x), FOLLOW_SYMLINKS_DEFAULT);
+            if let (Ok(a_stat), Ok(b_stat)) = (a_stat, b_stat) {
+                if stat::S_IFMT(a_stat.st_mode) != stat::S_IFMT(b_stat.st_mode) {
+                    delete(&mut self.conflicts, &dd.right.join(x), &rel.join(x))?;
+                    self.create(dd, &dd.left.join(x), &rel.join(x))?;
+                }
+            }
+        }
+        for x in &dd.diff_files {
+            self.creates.push(Op::Overwrite(rel.join(x)));
+        }
+        for x in &dd.same_files {
+            self.chmod(dd, x, rel)?;
+        }
+        for (x, sd) in &dd.subdirs {
+            self.walk(sd, &rel.join(x))?;
+            self.chmod(dd, x, rel)?;
+        }
+        for x in &dd.right_only {
+            delete(&mut self.deletes, &dd.right.join(x), &rel.join(x))?;
+        }
+        Ok(())
+    }
+
+    /// Create a source entry missing from the destination, with its
+    /// content if it is a directory, leaving out the names `dd` hides or
+    /// ignores.
+    fn create(&mut self, dd: &DirCmp, path: &Path, rel: &Path) -> io::Result<()> {
+        let st = os::stat(path, FOLLOW_SYMLINKS_DEFAULT)?;
+        if !stat::S_ISDIR(st.st_mode) {
+            self.creates.push(Op::Copy(rel.to_path_buf()));
+            return Ok(());
+        }
+        self.creates.push(Op::Mkdir(rel.to_path_buf()));
+        for name in filter_names(sorted_names(path)?, &dd.hide, &dd.ignore) {
+            self.create(dd, &path.join(&name), &rel.join(&name))?;
+        }
+        Ok(())
+    }
+
+    /// Align the permission bits of a common entry.
+    fn chmod(&mut self, dd: &DirCmp, x: &Path, rel: &Path) -> io::Result<()> {
+        let a_mode = stat::S_IMODE(os::stat(dd.left.join(x), FOLLOW_SYMLINKS_DEFAULT)?.st_mode);
+        let b_mode = stat::S_IMODE(os::stat(dd.right.join(x), FOLLOW_SYMLINKS_DEFAULT)?.st_mode);
+        if a_mode != b_mode {
+            self.chmods.push(Op::Chmod(rel.join(x), a_mode));
+        }
+        Ok(())
+    }
+}
+
+fn has_name_pairs(dd: &DirCmp) -> bool {
+    !dd.name_pairs.is_empty() || dd.subdirs.values().any(has_name_pairs)
+}
+
+/// Delete a destination entry, after the content of a directory. Symbolic
+/// links are deleted, not followed.
+fn delete(ops: &mut Vec<Op>, path: &Path, rel: &Path) -> io::Result<()> {
+    let st = os::stat(path, false)?;
+    if stat::S_ISDIR(st.st_mode) {
+        for name in sorted_names(path)? {
+            delete(ops, &path.join(&name), &rel.join(&name))?;
+        }
+    }
+    ops.push(Op::Delete(rel.to_path_buf()));
+    Ok(())
+}
+
+fn sorted_names(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut names = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| PathBuf::from(entry.file_name())))
+        .collect::<io::Result<Vec<_>>>()?;
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{DirCmpBuilder, NameMatching};
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_sync_plan() {
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_sync_plan");
+        let _ = fs::remove_dir_all(&test_dir);
+        let src = test_dir.join("src");
+        let dst = test_dir.join("dst");
+        fs::create_dir_all(src.join("new").join("deep")).unwrap();
+        fs::create_dir_all(src.join("kept")).unwrap();
+        fs::create_dir_all(dst.join("kept")).unwrap();
+        fs::create_dir_all(dst.join("stale")).unwrap();
+        fs::create_dir_all(dst.join("was_dir")).unwrap();
+        fs::write(src.join("new").join("deep").join("a.txt"), b"a").unwrap();
+        // ignored like in common directories
+        fs::create_dir_all(src.join("new").join(".git")).unwrap();
+        fs::write(src.join("new").join(".git").join("HEAD"), b"ref").unwrap();
+        fs::write(src.join("kept").join("diff.txt"), b"source").unwrap();
+        fs::write(dst.join("kept").join("diff.txt"), b"destination").unwrap();
+        fs::write(src.join("kept").join("mode.sh"), b"run").unwrap();
+        fs::write(dst.join("kept").join("mode.sh"), b"run").unwrap();
+        fs::write(dst.join("stale").join("old.txt"), b"old").unwrap();
+        fs::write(src.join("was_dir"), b"now a file").unwrap();
+        fs::write(dst.join("was_dir").join("inner.txt"), b"inner").unwrap();
+        let mode_sh = src.join("kept").join("mode.sh");
+        fs::set_permissions(&mode_sh, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(src.join("kept"), fs::Permissions::from_mode(0o750)).unwrap();
+        fs::set_permissions(dst.join("kept"), fs::Permissions::from_mode(0o755)).unwrap();
+        let mode_sh = dst.join("kept").join("mode.sh");
+        fs::set_permissions(&mode_sh, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let x = DirCmpBuilder::new(&src, &dst)
+            .shallow(false)
+            .build()
+            .unwrap();
+        let mut plan = SyncPlan::new(&x).unwrap();
+        let p = PathBuf::from;
+        assert_eq!(
+            plan.ops,
+            vec![
+                Op::Delete(p("was_dir/inner.txt")),
+                Op::Delete(p("was_dir")),
+                Op::Mkdir(p("new")),
+                Op::Mkdir(p("new/deep")),
+                Op::Copy(p("new/deep/a.txt")),
+                Op::Copy(p("was_dir")),
+                Op::Overwrite(p("kept/diff.txt")),
+                Op::Chmod(p("kept/mode.sh"), 0o755),
+                Op::Chmod(p("kept"), 0o750),
+                Op::Delete(p("stale/old.txt")),
+                Op::Delete(p("stale")),
+            ]
+        );
+
+        let x = DirCmpBuilder::new(&src, &dst)
+            .shallow(false)
+            .ignore(vec!["deep"])
+            .build()
+            .unwrap();
+        let ops = SyncPlan::new(&x).unwrap().ops;
+        assert!(ops.contains(&Op::Mkdir(p("new"))));
+        assert!(ops.contains(&Op::Mkdir(p("new/.git"))));
+        assert!(!ops.iter().any(|op| op.path().starts_with("new/deep")));
+
+        plan.retain(|op| !matches!(op, Op::Delete(path) if path.starts_with("stale")));
+        assert_eq!(plan.ops.len(), 9);
+        assert_eq!(plan.ops[0].path(), Path::new("was_dir/inner.txt"));
+
+        // paired names have no single destination path
+        fs::write(src.join("kept").join("Case.txt"), b"case").unwrap();
+        fs::write(dst.join("kept").join("case.txt"), b"case").unwrap();
+        let x = DirCmpBuilder::new(&src, &dst)
+            .name_matching(NameMatching::CaseInsensitive)
+            .build()
+            .unwrap();
+        let err = SyncPlan::new(&x).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_sync_execute() {
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_sync_execute");
+        let _ = fs::remove_dir_all(&test_dir);
+        let src = test_dir.join("src");
+        let dst = test_dir.join("dst");
+        fs::create_dir_all(src.join("new")).unwrap();
+        fs::create_dir_all(&dst).unwrap();
+        fs::write(src.join("new").join("a.txt"), b"a").unwrap();
+        fs::write(src.join("diff.txt"), b"source").unwrap();
+        fs::write(dst.join("diff.txt"), b"destination").unwrap();
+        fs::write(dst.join("stale.txt"), b"stale").unwrap();
+        let a_txt = src.join("new").join("a.txt");
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        File::open(&a_txt)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(mtime))
+            .unwrap();
+        fs::set_permissions(&a_txt, fs::Permissions::from_mode(0o400)).unwrap();
+        fs::set_permissions(src.join("new"), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let x = DirCmpBuilder::new(&src, &dst)
+            .shallow(false)
+            .build()
+            .unwrap();
+        let plan = SyncPlan::new(&x).unwrap();
+        let results = plan.execute(&SyncOptions::new().dry_run(true));
+        let log: Vec<String> = results.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            log,
+            vec![
+                "mkdir new: dry run",
+                "copy new/a.txt: dry run",
+                "overwrite diff.txt: dry run",
+                "delete stale.txt: skipped",
+            ]
+        );
+        assert!(!dst.join("new").exists());
+
+        // a link planted at a guessable temporary name is not followed
+        let victim = test_dir.join("victim");
+        fs::write(&victim, b"victim").unwrap();
+        let planted = format!(".diff.txt.filecmp-{}.tmp", process::id());
+        std::os::unix::fs::symlink(&victim, dst.join(&planted)).unwrap();
+
+        let results = plan.execute(&SyncOptions::new());
+        assert!(results
+            .iter()
+            .all(|r| matches!(r.status, Status::Done | Status::Skipped)));
+        let copy = dst.join("new").join("a.txt");
+        let meta = fs::metadata(&copy).unwrap();
+        assert_eq!(meta.modified().unwrap(), mtime);
+        assert_eq!(meta.permissions().mode() & 0o777, 0o400);
+        let meta = fs::metadata(dst.join("new")).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o555);
+        assert_eq!(fs::read(dst.join("diff.txt")).unwrap(), b"source");
+        assert!(dst.join("stale.txt").exists());
+        assert_eq!(fs::read(&victim).unwrap(), b"victim");
+        fs::remove_file(dst.join(&planted)).unwrap();
+        let names = sorted_names(&dst).unwrap();
+        assert!(names
+            .iter()
+            .all(|x| !x.to_string_lossy().contains(".filecmp-")));
+
+        let results = mirror(&src, &dst, &SyncOptions::new().delete(true)).unwrap();
+        let log: Vec<String> = results.iter().map(ToString::to_string).collect();
+        assert_eq!(log, vec!["delete stale.txt: done"]);
+        let x = DirCmpBuilder::new(&src, &dst)
+            .shallow(false)
+            .build()
+            .unwrap();
+        assert!(x.left_only.is_empty() && x.right_only.is_empty() && x.diff_files.is_empty());
+
+        for dir in &[&src, &dst] {
+            fs::set_permissions(dir.join("new"), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+}
@@ -0,0 +1,528 @@
+//! File system abstraction for the sources of a comparison.
+//!
+//! `DirCmp` and `cmp_with()` read trees through the `FileSystem` trait:
+//!  - RealFs -- the real file system, through os.stat() and `File::open`
+//!  - MemFs -- a tree held in memory, for archives and generated fixtures
+//!  - manifest::ManifestFs -- a stored manifest, compared by digest
+//!
+//! Other sources such as git trees can be compared by implementing
+//! `FileSystem` and passing it to `DirCmpBuilder::left_fs()` or
+//! `DirCmpBuilder::right_fs()`, or to `vfs::cmp()` for single files.
+//!
+//! # Example
+//!
+//! ```rust
+//! use filecmp::vfs::MemFs;
+//! use filecmp::DirCmpBuilder;
+//!
+//! let mut left = MemFs::new("left");
+//! left.add_file("same.txt", "same");
+//! left.add_file("sub/diff.txt", "left");
+//! let mut right = MemFs::new("right");
+//! right.add_file("same.txt", "same");
+//! right.add_file("sub/diff.txt", "right");
+//!
+//! let x = DirCmpBuilder::new("left", "right")
+//!     .left_fs(left)
+//!     .right_fs(right)
+//!     .shallow(false)
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(x.same_files, vec![std::path::PathBuf::from("same.txt")]);
+//! assert_eq!(x.subdirs[std::path::Path::new("sub")].diff_files.len(), 1);
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+pub use crate::os::StatResult;
+pub use crate::xattr::Xattrs;
+use crate::{os, stat, xattr, CmpOptions};
+
+/// Maximum number of symbolic links followed while resolving a path.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// Read access to a tree of files.
+pub trait FileSystem: fmt::Debug + Send + Sync {
+    /// Return the entry names of a directory, like os.listdir().
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Return the status of an entry, like os.stat().
+    fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<StatResult>;
+
+    /// Open a regular file for reading its content.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Return the target of a symbolic link, like os.readlink(). File
+    /// systems without links report an error.
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{}: symbolic links are not supported", path.display()),
+        ))
+    }
+
+    /// Return the SHA-256 digest of a regular file in lowercase hex, if it
+    /// is known without reading the file. Comparisons use it when either
+    /// side has one, such as a manifest which has no content to read.
+    fn digest(&self, path: &Path) -> io::Result<Option<String>> {
+        self.stat(path, true)?;
+        Ok(None)
+    }
+
+    /// Return the extended attributes of an entry, following symbolic
+    /// links. File systems without them report none.
+    fn xattrs(&self, path: &Path) -> io::Result<Xattrs> {
+        self.stat(path, true)?;
+        Ok(Xattrs::new())
+    }
+}
+
+/// Compare two files living on any file systems.
+///
+/// Like `cmp_with()`, but without the cache, since entries of other file
+/// systems have no stable identity.
+pub fn cmp(
+    fs1: &dyn FileSystem,
+    f1: impl AsRef<Path>,
+    fs2: &dyn FileSystem,
+    f2: impl AsRef<Path>,
+    options: &CmpOptions,
+) -> io::Result<bool> {
+    crate::cmp_in(fs1, f1.as_ref(), fs2, f2.as_ref(), options, false)
+}
+
+/// The real file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+impl FileSystem for RealFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| PathBuf::from(entry.file_name())))
+            .collect()
+    }
+
+    fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<StatResult> {
+        os::stat(path, follow_symlinks)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn xattrs(&self, path: &Path) -> io::Result<Xattrs> {
+        xattr::list(path)
+    }
+}
+
+/// A tree of files held in memory.
+///
+/// Entries are added relative to the root of the tree, and looked up
+/// below the root path, so entry `src/lib.rs` of `MemFs::new("crate")` is
+/// found at `crate/src/lib.rs`. Missing parent directories are created on
+/// the fly. Symbolic links are resolved inside the tree; absolute targets
+/// are dangling.
+#[derive(Debug, Clone)]
+pub struct MemFs {
+    root: PathBuf,
+    entries: BTreeMap<PathBuf, MemEntry>,
+}
+
+/// An entry of a `MemFs`, with setters for its metadata.
+#[derive(Debug, Clone)]
+pub struct MemEntry {
+    node: Node,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: f64,
+    xattrs: Xattrs,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Dir,
+    File(Vec<u8>),
+    Symlink(PathBuf),
+    /// Devices, fifos and the like, by their S_IFMT type.
+    Other(u32),
+}
+
+impl MemFs {
+    /// Create an empty tree found at `root`.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        let mut entries = BTreeMap::new();
+        entries.insert(PathBuf::new(), MemEntry::new(Node::Dir));
+        MemFs {
+            root: root.as_ref().to_path_buf(),
+            entries,
+        }
+    }
+
+    /// The path at which the tree is found.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Add a directory.
+    ///
+    /// Panics if `path` is empty or leaves the tree through `..`.
+    pub fn add_dir(&mut self, path: impl AsRef<Path>) -> &mut MemEntry {
+        self.insert(path.as_ref(), Node::Dir)
+    }
+
+    /// Add a regular file with the given content.
+    ///
+    /// Panics if `path` is empty or leaves the tree through `..`.
+    pub fn add_file(&mut self, path: impl AsRef<Path>, data: impl Into<Vec<u8>>) -> &mut MemEntry {
+        self.insert(path.as_ref(), Node::File(data.into()))
+    }
+
+    /// Add a symbolic link pointing to `target`.
+    ///
+    /// Panics if `path` is empty or leaves the tree through `..`.
+    pub fn add_symlink(
+        &mut self,
+        path: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+    ) -> &mut MemEntry {
+        self.insert(path.as_ref(), Node::Symlink(target.as_ref().to_path_buf()))
+    }
+
+    /// Add a device, fifo or socket of the given S_IFMT type, such as
+    /// `S_IFIFO` (0o010000).
+    ///
+    /// Panics if `path` is empty or leaves the tree through `..`.
+    pub fn add_special(&mut self, path: impl AsRef<Path>, ifmt: u32) -> &mut MemEntry {
+        self.insert(path.as_ref(), Node::Other(ifmt))
+    }
+
+    fn insert(&mut self, path: &Path, node: Node) -> &mut MemEntry {
+        let name = relative_path(path)
+            .unwrap_or_else(|| panic!("{}: not a path inside the tree", path.display()));
+        for parent in name.ancestors().skip(1) {
+            self.entries
+                .entry(parent.to_path_buf())
+                .or_insert_with(|| MemEntry::new(Node::Dir));
+        }
+        self.entries.insert(name.clone(), MemEntry::new(node));
+        self.entries
+            .get_mut(&name)
+            .expect("entry was just inserted")
+    }
+
+    /// Find the entry at `path`, following symbolic links for all
+    /// components and, if asked to, for the last one.
+    fn resolve(&self, path: &Path, follow_symlinks: bool) -> io::Result<PathBuf> {
+        resolve(&self.root, path, follow_symlinks, |rel| {
+            self.entries.get(rel).map(|entry| match &entry.node {
+                Node::Symlink(target) => Some(target.as_path()),
+                _ => None,
+            })
+        })
+    }
+
+    fn entry(&self, path: &Path, follow_symlinks: bool) -> io::Result<&MemEntry> {
+        Ok(&self.entries[&self.resolve(path, follow_symlinks)?])
+    }
+}
+
+impl FileSystem for MemFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let dir = self.resolve(path, true)?;
+        match self.entries[&dir].node {
+            Node::Dir => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}: not a directory", path.display()),
+                ))
+            }
+        }
+        let names = self
+            .entries
+            .range(dir.clone()..)
+            .skip(1)
+            .take_while(|(name, _)| name.starts_with(&dir))
+            .filter(|(name, _)| name.parent() == Some(&dir))
+            .filter_map(|(name, _)| name.file_name().map(PathBuf::from))
+            .collect();
+        Ok(names)
+    }
+
+    fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<StatResult> {
+        let entry = self.entry(path, follow_symlinks)?;
+        let (ifmt, size) = match &entry.node {
+            Node::Dir => (stat::S_IFDIR, 0),
+            Node::File(data) => (stat::S_IFREG, data.len() as u64),
+            Node::Symlink(target) => (stat::S_IFLNK, target.as_os_str().len() as u64),
+            Node::Other(ifmt) => (*ifmt, 0),
+        };
+        Ok(StatResult {
+            st_mode: ifmt | entry.mode,
+            st_ino: 0,
+            st_dev: 0,
+            st_nlink: 1,
+            st_uid: entry.uid,
+            st_gid: entry.gid,
+            st_size: size,
+            st_atime: entry.mtime,
+            st_mtime: entry.mtime,
+            st_ctime: entry.mtime,
+        })
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match &self.entry(path, true)?.node {
+            Node::File(data) => Ok(Box::new(&data[..])),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: not a regular file", path.display()),
+            )),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match &self.entry(path, false)?.node {
+            Node::Symlink(target) => Ok(target.clone()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: not a symbolic link", path.display()),
+            )),
+        }
+    }
+
+    fn xattrs(&self, path: &Path) -> io::Result<Xattrs> {
+        Ok(self.entry(path, true)?.xattrs.clone())
+    }
+}
+
+impl MemEntry {
+    fn new(node: Node) -> Self {
+        let mode = match node {
+            Node::Dir => 0o755,
+            Node::Symlink(_) => 0o777,
+            _ => 0o644,
+        };
+        MemEntry {
+            node,
+            mode,
+            uid: 0,
+            gid: 0,
+            mtime: 0.0,
+            xattrs: Xattrs::new(),
+        }
+    }
+
+    /// Set the permission bits, as returned by `S_IMODE()`.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = stat::S_IMODE(mode);
+        self
+    }
+
+    /// Set the owning user and group.
+    pub fn owner(&mut self, uid: u32, gid: u32) -> &mut Self {
+        self.uid = uid;
+        self.gid = gid;
+        self
+    }
+
+    /// Set the modification time, in seconds since the Unix epoch.
+    pub fn mtime(&mut self, mtime: f64) -> &mut Self {
+        self.mtime = mtime;
+        self
+    }
+
+    /// Set an extended attribute.
+    pub fn xattr(&mut self, name: impl Into<OsString>, value: impl Into<Vec<u8>>) -> &mut Self {
+        self.xattrs.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// Normalise a path to a relative one inside a tree, or None if it is
+/// empty or escapes the tree.
+pub(crate) fn relative_path(path: &Path) -> Option<PathBuf> {
+    let mut rel = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => rel.push(name),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+            Component::ParentDir => return None,
+        }
+    }
+    if rel.as_os_str().is_empty() {
+        None
+    } else {
+        Some(rel)
+    }
+}
+
+/// Find the relative path of the entry at `path` in a tree rooted at
+/// `root`, following symbolic links for all components and, if asked to,
+/// for the last one.
+///
+/// `lookup` tells whether a relative path exists, and its target if it is
+/// a symbolic link. Targets are resolved inside the tree; absolute targets
+/// are dangling.
+pub(crate) fn resolve<'a>(
+    root: &Path,
+    path: &Path,
+    follow_symlinks: bool,
+    lookup: impl Fn(&Path) -> Option<Option<&'a Path>>,
+) -> io::Result<PathBuf> {
+    let rel = path.strip_prefix(root).map_err(|_| not_found(path))?;
+    let mut pending: VecDeque<OsString> = names(rel).collect();
+    let mut resolved = PathBuf::new();
+    let mut hops = 0;
+    while let Some(name) = pending.pop_front() {
+        if name == ".." {
+            resolved.pop();
+            continue;
+        }
+        let candidate = resolved.join(&name);
+        match lookup(&candidate) {
+            None => return Err(not_found(path)),
+            Some(Some(target)) if follow_symlinks || !pending.is_empty() => {
+                hops += 1;
+                if hops > MAX_SYMLINK_HOPS || target.has_root() {
+                    return Err(not_found(path));
+                }
+                for name in names(target).rev() {
+                    pending.push_front(name);
+                }
+            }
+            Some(_) => resolved = candidate,
+        }
+    }
+    Ok(resolved)
+}
+
+/// The components of a path as names, keeping `..` but not `.` or `/`.
+fn names(path: &Path) -> impl DoubleEndedIterator<Item = OsString> + '_ {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name.to_os_string()),
+        Component::ParentDir => Some(OsString::from("..")),
+        Component::CurDir | Component::RootDir | Component::Prefix(_) => None,
+    })
+}
+
+pub(crate) fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{}: no such file or directory", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_memfs() {
+        let mut tree = MemFs::new("/fixture");
+        tree.add_file("docs/readme.txt", "hello")
+            .mode(0o600)
+            .mtime(42.0);
+        tree.add_symlink("latest", "docs/readme.txt");
+        tree.add_symlink("loop", "loop");
+        tree.add_special("pipe", stat::S_IFIFO);
+
+        assert_eq!(
+            tree.read_dir(Path::new("/fixture")).unwrap(),
+            vec![
+                PathBuf::from("docs"),
+                "latest".into(),
+                "loop".into(),
+                "pipe".into()
+            ]
+        );
+        let st = tree.stat(Path::new("/fixture/latest"), true).unwrap();
+        assert_eq!(st.st_mode, stat::S_IFREG | 0o600);
+        assert_eq!(st.st_size, 5);
+        assert_eq!(st.st_mtime, 42.0);
+        let st = tree.stat(Path::new("/fixture/latest"), false).unwrap();
+        assert!(stat::S_ISLNK(st.st_mode));
+        assert!(tree.stat(Path::new("/fixture/loop"), true).is_err());
+        assert!(tree.stat(Path::new("/elsewhere/latest"), true).is_err());
+        assert!(tree.read_dir(Path::new("/fixture/latest")).is_err());
+
+        let test_dir = env::temp_dir().join("test_filecmp").join("test_memfs");
+        fs::create_dir_all(&test_dir).unwrap();
+        fs::write(test_dir.join("readme.txt"), b"hello").unwrap();
+        let options = CmpOptions::new().shallow(false);
+        let on_disk = test_dir.join("readme.txt");
+        assert!(cmp(&tree, "/fixture/latest", &RealFs, &on_disk, &options).unwrap());
+        assert!(!cmp(&tree, "/fixture/pipe", &RealFs, &on_disk, &options).unwrap());
+
+        let mut other = MemFs::new("/other");
+        other.add_file("docs/readme.txt", "hello");
+        other.add_file("latest", "hello");
+        other.add_symlink("loop", "elsewhere");
+        let builder = crate::DirCmpBuilder::new("/fixture", "/other")
+            .left_fs(tree.clone())
+            .right_fs(other)
+            .ignore(vec!["pipe"])
+            .shallow(false);
+        let x = builder.build().unwrap();
+        assert_eq!(x.same_files, vec![PathBuf::from("latest")]);
+        assert_eq!(x.common_funny, vec![PathBuf::from("loop")]);
+        let x = builder.follow_symlinks(false).build().unwrap();
+        assert_eq!(x.common_funny, vec![PathBuf::from("latest")]);
+        assert_eq!(x.diff_files, vec![PathBuf::from("loop")]);
+        assert_eq!(
+            tree.read_link(Path::new("/fixture/latest")).unwrap(),
+            Path::new("docs/readme.txt")
+        );
+        assert!(tree.read_link(Path::new("/fixture/pipe")).is_err());
+    }
+
+    #[test]
+    fn test_memfs_links() {
+        // a chain of MAX_SYMLINK_HOPS links resolves, one more does not
+        let mut tree = MemFs::new("/fixture");
+        tree.add_file("target", "data");
+        let mut previous = String::from("target");
+        for i in 0..=MAX_SYMLINK_HOPS {
+            let name = format!("hop{}", i);
+            tree.add_symlink(&name, &previous);
+            previous = name;
+        }
+        let last = format!("/fixture/hop{}", MAX_SYMLINK_HOPS - 1);
+        assert_eq!(tree.stat(Path::new(&last), true).unwrap().st_size, 4);
+        let over = format!("/fixture/hop{}", MAX_SYMLINK_HOPS);
+        let err = tree.stat(Path::new(&over), true).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(tree.stat(Path::new(&over), false).is_ok());
+
+        // paths and targets cannot leave the tree: `..` stops at its root,
+        // absolute targets dangle
+        tree.add_symlink("dir/up", "../../../target");
+        tree.add_symlink("absolute", "/fixture/target");
+        let st = tree.stat(Path::new("/fixture/dir/up"), true).unwrap();
+        assert_eq!(st.st_size, 4);
+        assert!(tree.stat(Path::new("/fixture/absolute"), true).is_err());
+        assert!(tree.stat(Path::new("/fixture/../target"), true).is_ok());
+        assert!(tree.stat(Path::new("/fixture/../elsewhere"), true).is_err());
+        assert!(tree.stat(Path::new("/elsewhere/target"), true).is_err());
+        assert_eq!(
+            relative_path(Path::new("/a/./b")),
+            Some(PathBuf::from("a/b"))
+        );
+        assert_eq!(relative_path(Path::new("a/../b")), None);
+        let escaping = std::panic::catch_unwind(|| {
+            MemFs::new("/x").add_file("../out", "");
+        });
+        assert!(escaping.is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! Streaming directory comparison through callbacks.
+//!
+//! `DirCmpBuilder::visit()` walks both trees like `DirCmpBuilder::build()`,
+//! but reports every entry to a `Visitor` as soon as it is classified,
+//! instead of collecting a `DirCmp` tree. Only the listing of the current
+//! directories is held in memory.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::env;
+//! use std::fs;
+//! use std::ops::ControlFlow;
+//! use std::path::{Path, PathBuf};
+//! use filecmp::visit::Visitor;
+//! use filecmp::DirCmpBuilder;
+//!
+//! /// Stop at the first difference of any kind.
+//! #[derive(Default)]
+//! struct FirstDifference(Option<PathBuf>);
+//!
+//! impl Visitor for FirstDifference {
+//!     fn left_only(&mut self, path: &Path) -> ControlFlow<()> {
+//!         self.0 = Some(path.to_path_buf());
+//!         ControlFlow::Break(())
+//!     }
+//!     fn right_only(&mut self, path: &Path) -> ControlFlow<()> {
+//!         self.left_only(path)
+//!     }
+//!     fn different(&mut self, path: &Path) -> ControlFlow<()> {
+//!         self.left_only(path)
+//!     }
+//! }
+//!
+//! let temp_dir = env::temp_dir().join("filecmp_visit_example");
+//! fs::create_dir_all(temp_dir.join("a")).unwrap();
+//! fs::create_dir_all(temp_dir.join("b")).unwrap();
+//! fs::write(temp_dir.join("a").join("only_a.txt"), b"a").unwrap();
+//!
+//! let mut visitor = FirstDifference::default();
+//! let flow = DirCmpBuilder::new(temp_dir.join("a"), temp_dir.join("b"))
+//!     .visit(&mut visitor)
+//!     .unwrap();
+//! assert!(flow.is_break());
+//! assert_eq!(visitor.0, Some(PathBuf::from("only_a.txt")));
+//! ```
+
+use std::io;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use crate::{DirCmpBuilder, Source};
+
+/// Callbacks of `DirCmpBuilder::visit()`.
+///
+/// Paths are relative to the roots of both trees, under their left names
+/// when `DirCmpBuilder::name_matching()` pairs names; the roots themselves
+/// are the empty path. Every callback continues by default, and returning
+/// `ControlFlow::Break` stops the walk right away.
+pub trait Visitor {
+    /// Called before the entries of a directory found in both trees.
+    fn enter_dir(&mut self, path: &Path) -> ControlFlow<()> {
+        let _ = path;
+        ControlFlow::Continue(())
+    }
+
+    /// Called after all entries of a directory, including subdirectories.
+    fn leave_dir(&mut self, path: &Path) -> ControlFlow<()> {
+        let _ = path;
+        ControlFlow::Continue(())
+    }
+
+    /// An entry only found in the left tree. Directories are not walked.
+    fn left_only(&mut self, path: &Path) -> ControlFlow<()> {
+        let _ = path;
+        ControlFlow::Continue(())
+    }
+
+    /// An entry only found in the right tree. Directories are not walked.
+    fn right_only(&mut self, path: &Path) -> ControlFlow<()> {
+        let _ = path;
+        ControlFlow::Continue(())
+    }
+
+    /// A file which is the same in both trees.
+    fn same(&mut self, path: &Path) -> ControlFlow<()> {
+        let _ = path;
+        ControlFlow::Continue(())
+    }
+
+    /// A file which differs between both trees.
+    fn different(&mut self, path: &Path) -> ControlFlow<()> {
+        let _ = path;
+        ControlFlow::Continue(())
+    }
+
+    /// An entry which could not be compared: of another type in each
+    /// tree, neither file nor directory, or not readable.
+    fn funny(&mut self, path: &Path) -> ControlFlow<()> {
+        let _ = path;
+        ControlFlow::Continue(())
+    }
+}
+
+/// Return from the walk if a callback breaks.
+macro_rules! call {
+    ($flow:expr) => {
+        if let ControlFlow::Break(()) = $flow {
+            return Ok(ControlFlow::Break(()));
+        }
+    };
+}
+
+impl DirCmpBuilder {
+    /// Walk both directories, reporting every entry to `visitor`.
+    ///
+    /// Entries come in the order of a `DirCmp` report: left only, right
+    /// only, common files, funny cases, then subdirectories. Rename
+    /// detection is not available here. Returns `ControlFlow::Break` if
+    /// the visitor stopped the walk.
+    pub fn visit(&self, visitor: &mut dyn Visitor) -> io::Result<ControlFlow<()>> {
+        let left_src = Source::open(&self.left_fs, &self.left, self.archives)?;
+        let right_src = Source::open(&self.right_fs, &self.right, self.archives)?;
+        self.visit_at(
+            &left_src,
+            &self.left,
+            &right_src,
+            &self.right,
+            Path::new(""),
+            visitor,
+        )
+    }
+
+    fn visit_at(
+        &self,
+        left_src: &Source,
+        a: &Path,
+        right_src: &Source,
+        b: &Path,
+        rel: &Path,
+        visitor: &mut dyn Visitor,
+    ) -> io::Result<ControlFlow<()>> {
+        let dd = self.list_level(left_src, a, right_src, b)?;
+        call!(visitor.enter_dir(rel));
+        for x in &dd.left_only {
+            call!(visitor.left_only(&rel.join(x)));
+        }
+        for x in &dd.right_only {
+            call!(visitor.right_only(&rel.join(x)));
+        }
+        for x in &dd.common_files {
+            let path = rel.join(x);
+            let y = dd.right_name(x);
+            call!(
+                match self.cmp_entry(left_src, &a.join(x), right_src, &b.join(y)) {
+                    Ok(true) => visitor.same(&path),
+                    Ok(false) => visitor.different(&path),
+                    Err(_) => visitor.funny(&path),
+                }
+            );
+        }
+        for x in dd.funny_files.iter().chain(&dd.common_funny) {
+            call!(visitor.funny(&rel.join(x)));
+        }
+        for x in &dd.common_dirs {
+            call!(self.visit_at(
+                left_src,
+                &a.join(x),
+                right_src,
+                &b.join(dd.right_name(x)),
+                &rel.join(x),
+                visitor
+            )?);
+        }
+        call!(visitor.leave_dir(rel));
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemFs;
+    use crate::NameMatching;
+    use std::path::PathBuf;
+
+    #[derive(Default)]
+    struct Events {
+        log: Vec<String>,
+        stop_at: Option<PathBuf>,
+    }
+
+    impl Events {
+        fn push(&mut self, event: &str, path: &Path) -> ControlFlow<()> {
+            self.log.push(format!("{} {}", event, path.display()));
+            if self.stop_at.as_deref() == Some(path) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    impl Visitor for Events {
+        fn enter_dir(&mut self, path: &Path) -> ControlFlow<()> {
+            self.push("enter", path)
+        }
+        fn leave_dir(&mut self, path: &Path) -> ControlFlow<()> {
+            self.push("leave", path)
+        }
+        fn left_only(&mut self, path: &Path) -> ControlFlow<()> {
+            self.push("left_only", path)
+        }
+        fn right_only(&mut self, path: &Path) -> ControlFlow<()> {
+            self.push("right_only", path)
+        }
+        fn same(&mut self, path: &Path) -> ControlFlow<()> {
+            self.push("same", path)
+        }
+        fn different(&mut self, path: &Path) -> ControlFlow<()> {
+            self.push("different", path)
+        }
+        fn funny(&mut self, path: &Path) -> ControlFlow<()> {
+            self.push("funny", path)
+        }
+    }
+
+    #[test]
+    fn test_visit() {
+        let mut left = MemFs::new("left");
+        left.add_file("same.txt", "same");
+        left.add_file("sub/diff.txt", "left");
+        left.add_file("sub/deeper/only_left.txt", "left");
+        left.add_file("kind", "file");
+        let mut right = MemFs::new("right");
+        right.add_file("same.txt", "same");
+        right.add_file("sub/diff.txt", "right");
+        right.add_dir("sub/deeper");
+        right.add_dir("kind");
+        right.add_file("only_right.txt", "right");
+        let builder = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .shallow(false);
+
+        let mut events = Events::default();
+        let flow = builder.visit(&mut events).unwrap();
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(
+            events.log,
+            vec![
+                "enter ",
+                "right_only only_right.txt",
+                "same same.txt",
+                "funny kind",
+                "enter sub",
+                "different sub/diff.txt",
+                "enter sub/deeper",
+                "left_only sub/deeper/only_left.txt",
+                "leave sub/deeper",
+                "leave sub",
+                "leave ",
+            ]
+        );
+
+        let mut events = Events {
+            stop_at: Some(PathBuf::from("sub/diff.txt")),
+            ..Events::default()
+        };
+        let flow = builder.visit(&mut events).unwrap();
+        assert_eq!(flow, ControlFlow::Break(()));
+        assert_eq!(events.log.last().unwrap(), "different sub/diff.txt");
+        assert_eq!(events.log.len(), 6);
+    }
+
+    #[test]
+    fn test_visit_name_pairs() {
+        let mut left = MemFs::new("left");
+        left.add_file("File", "same");
+        left.add_file("Sub/f", "left");
+        let mut right = MemFs::new("right");
+        right.add_file("file", "same");
+        right.add_file("sub/f", "right");
+        let builder = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .shallow(false)
+            .name_matching(NameMatching::CaseInsensitive);
+
+        let mut events = Events::default();
+        let flow = builder.visit(&mut events).unwrap();
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(
+            events.log,
+            vec![
+                "enter ",
+                "same File",
+                "enter Sub",
+                "different Sub/f",
+                "leave Sub",
+                "leave ",
+            ]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Extended attributes, read with listxattr() and getxattr() on Linux.
+//!
+//! Other platforms report no attributes, so that enabling the comparison
+//! there is harmless.
+
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::Path;
+
+use crate::digest;
+
+/// Extended attributes of an entry, by name.
+pub type Xattrs = BTreeMap<OsString, Vec<u8>>;
+
+/// Differences between the extended attributes of two entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XattrDiff {
+    /// Names only set on the right entry.
+    pub added: Vec<OsString>,
+    /// Names only set on the left entry.
+    pub removed: Vec<OsString>,
+    /// Names set on both entries, with different values.
+    pub changed: Vec<OsString>,
+}
+
+impl XattrDiff {
+    /// Compare two sets of attributes, keeping the names matched by
+    /// `patterns` (see `CmpOptions::xattrs()`) but the digest cache.
+    pub fn new(x1: &Xattrs, x2: &Xattrs, patterns: &[String]) -> Self {
+        let mut diff = XattrDiff::default();
+        for (name, value) in x1.iter().filter(|(name, _)| matches(patterns, name)) {
+            match x2.get(name) {
+                None => diff.removed.push(name.clone()),
+                Some(other) if other != value => diff.changed.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.added = x2
+            .keys()
+            .filter(|name| matches(patterns, name) && !x1.contains_key(*name))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// Whether the attributes are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Whether an attribute name is matched by any pattern: a full name, a
+/// namespace such as `user.*`, or `*`. The digest cache of
+/// `CmpOptions::digest_cache()` is never matched, as its value holds the
+/// inode of the file.
+fn matches(patterns: &[String], name: &OsStr) -> bool {
+    if name == digest::CACHE_XATTR {
+        return false;
+    }
+    let name = name.to_string_lossy();
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern.as_str(),
+        })
+}
+
+/// List the extended attributes of a file, following symbolic links.
+///
+/// File systems without extended attributes report none.
+#[cfg(target_os = "linux")]
+pub fn list(path: &Path) -> io::Result<Xattrs> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let names = match read_buf(|buf, size| unsafe {
+        libc::listxattr(path.as_ptr(), buf as *mut libc::c_char, size)
+    }) {
+        Ok(names) => names,
+        Err(err) if err.raw_os_error() == Some(libc::ENOTSUP) => return Ok(Xattrs::new()),
+        Err(err) => return Err(err),
+    };
+    let mut xattrs = Xattrs::new();
+    for name in names.split(|&b| b == 0).filter(|name| !name.is_empty()) {
+        let c_name = CString::new(name)?;
+        let value = match read_buf(|buf, size| unsafe {
+            libc::getxattr(path.as_ptr(), c_name.as_ptr(), buf, size)
+        }) {
+            Ok(value) => value,
+            // removed since it was listed
+            Err(err) if err.raw_os_error() == Some(libc::ENODATA) => continue,
+            Err(err) => return Err(err),
+        };
+        xattrs.insert(OsString::from_vec(name.to_vec()), value);
+    }
+    Ok(xattrs)
+}
+
+/// List the extended attributes of a file, which are not supported here.
+#[cfg(not(target_os = "linux"))]
+pub fn list(_path: &Path) -> io::Result<Xattrs> {
+    Ok(Xattrs::new())
+}
+
+/// Read one extended attribute of a file, following symbolic links, or
+/// None if it is not set or not supported.
+#[cfg(target_os = "linux")]
+pub fn get(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    match read_buf(|buf, size| unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size) }) {
+        Ok(value) => Ok(Some(value)),
+        Err(err)
+            if matches!(
+                err.raw_os_error(),
+                Some(libc::ENODATA) | Some(libc::ENOTSUP)
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Read one extended attribute of a file, which are not supported here.
+#[cfg(not(target_os = "linux"))]
+pub fn get(_path: &Path, _name: &str) -> io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+/// Set one extended attribute of a file, following symbolic links.
+#[cfg(target_os = "linux")]
+pub fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    let ret = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+            0,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Set one extended attribute of a file, which are not supported here.
+#[cfg(not(target_os = "linux"))]
+pub fn set(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes are not supported",
+    ))
+}
+
+/// Call a listxattr()-like function, growing the buffer until the whole
+/// value fits.
+#[cfg(target_os = "linux")]
+fn read_buf(
+    call: impl Fn(*mut libc::c_void, libc::size_t) -> libc::ssize_t,
+) -> io::Result<Vec<u8>> {
+    loop {
+        let size = call(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0u8; size as usize];
+        let len = call(buf.as_mut_ptr() as *mut libc::c_void, buf.len());
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ERANGE) {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemFs;
+    use crate::DirCmpBuilder;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_xattr_diff() {
+        let attrs = |pairs: &[(&str, &[u8])]| -> Xattrs {
+            pairs
+                .iter()
+                .map(|(name, value)| (OsString::from(name), value.to_vec()))
+                .collect()
+        };
+        let x1 = attrs(&[
+            ("security.capability", b"cap_net_bind"),
+            ("security.selinux", b"system_u:object_r:bin_t"),
+            ("user.comment", b"old"),
+            ("user.filecmp.sha256", b"1 2 3 digest"),
+            ("user.origin", b"build"),
+        ]);
+        let x2 = attrs(&[
+            ("security.selinux", b"system_u:object_r:usr_t"),
+            ("trusted.overlay.opaque", b"y"),
+            ("user.comment", b"new"),
+            ("user.origin", b"build"),
+        ]);
+
+        let diff = XattrDiff::new(&x1, &x2, &["*".to_string()]);
+        assert_eq!(diff.added, vec![OsString::from("trusted.overlay.opaque")]);
+        assert_eq!(diff.removed, vec![OsString::from("security.capability")]);
+        assert_eq!(
+            diff.changed,
+            vec![
+                OsString::from("security.selinux"),
+                OsString::from("user.comment")
+            ]
+        );
+
+        let diff = XattrDiff::new(&x1, &x2, &["user.origin".to_string()]);
+        assert!(diff.is_empty());
+        let diff = XattrDiff::new(&x1, &x2, &["security.*".to_string()]);
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.changed, vec![OsString::from("security.selinux")]);
+        assert!(XattrDiff::new(&x1, &x2, &[]).is_empty());
+
+        let mut left = MemFs::new("left");
+        left.add_file("ping", "elf")
+            .xattr("security.capability", "cap_net_raw");
+        left.add_dir("etc").xattr("security.selinux", "etc_t");
+        let mut right = MemFs::new("right");
+        right.add_file("ping", "elf");
+        right.add_dir("etc").xattr("security.selinux", "etc_t");
+        let x = DirCmpBuilder::new("left", "right")
+            .left_fs(left)
+            .right_fs(right)
+            .xattrs(vec!["security.*"])
+            .build()
+            .unwrap();
+        assert_eq!(x.diff_files, vec![PathBuf::from("ping")]);
+        assert_eq!(
+            x.xattr_diffs.keys().collect::<Vec<_>>(),
+            vec![Path::new("ping")]
+        );
+        assert_eq!(
+            x.xattr_diffs[Path::new("ping")].removed,
+            vec![OsString::from("security.capability")]
+        );
+    }
+
+    #[test]
+    fn test_digest_cache_xattr() {
+        let test_dir = env::temp_dir()
+            .join("test_filecmp")
+            .join("test_digest_cache_xattr");
+        let _ = fs::remove_dir_all(&test_dir);
+        let (a, b) = (test_dir.join("a"), test_dir.join("b"));
+        for dir in &[&a, &b] {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join("file"), "same").unwrap();
+        }
+        // the second run finds digests cached with different inodes
+        for _ in 0..2 {
+            let x = DirCmpBuilder::new(&a, &b)
+                .shallow(false)
+                .digest_cache(true)
+                .xattrs(vec!["user.*"])
+                .build()
+                .unwrap();
+            assert_eq!(x.same_files, vec![PathBuf::from("file")]);
+            assert!(x.xattr_diffs.is_empty());
+        }
+    }
+}
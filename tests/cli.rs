@@ -0,0 +1,144 @@
+//! Tests of the `filecmp` binary and its exit status: 0 if the operands
+//! are the same, 1 if they differ, 2 on trouble.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn filecmp(args: &[&dyn AsRef<std::ffi::OsStr>]) -> Output {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_filecmp"));
+    for arg in args {
+        cmd.arg(arg);
+    }
+    cmd.output().unwrap()
+}
+
+fn status(output: &Output) -> i32 {
+    output.status.code().unwrap()
+}
+
+/// A fresh test directory with trees `a` and `b`, which differ in
+/// `sub/diff.txt` and a `CVS` directory only in `a`.
+fn trees(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+    let test_dir = env::temp_dir().join("test_filecmp").join(name);
+    let _ = fs::remove_dir_all(&test_dir);
+    let (a, b) = (test_dir.join("a"), test_dir.join("b"));
+    for dir in &[&a, &b] {
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("same.txt"), "same").unwrap();
+    }
+    fs::write(a.join("sub").join("diff.txt"), "left").unwrap();
+    fs::write(b.join("sub").join("diff.txt"), "right").unwrap();
+    fs::create_dir_all(a.join("CVS")).unwrap();
+    (test_dir, a, b)
+}
+
+#[test]
+fn test_files() {
+    let (test_dir, a, b) = trees("test_cli_files");
+    let same = a.join("same.txt");
+    let diff = (
+        a.join("sub").join("diff.txt"),
+        b.join("sub").join("diff.txt"),
+    );
+
+    assert_eq!(status(&filecmp(&[&same, &b.join("same.txt")])), 0);
+    let output = filecmp(&[&"-d", &diff.0, &diff.1]);
+    assert_eq!(status(&output), 1);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("differ"));
+    let output = filecmp(&[&"-q", &diff.0, &diff.1]);
+    assert_eq!(status(&output), 1);
+    assert!(output.stdout.is_empty());
+    assert_eq!(status(&filecmp(&[&same, &test_dir.join("missing")])), 2);
+    assert_eq!(status(&filecmp(&[&"--no-such-option", &same, &same])), 2);
+
+    // -P compares links by their targets
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+        let (l1, l2) = (test_dir.join("l1"), test_dir.join("l2"));
+        symlink(&same, &l1).unwrap();
+        symlink(b.join("same.txt"), &l2).unwrap();
+        assert_eq!(status(&filecmp(&[&"-d", &l1, &l2])), 0);
+        assert_eq!(status(&filecmp(&[&"-P", &l1, &l2])), 1);
+        assert_eq!(status(&filecmp(&[&"-P", &l1, &l1])), 0);
+        assert_eq!(status(&filecmp(&[&"-P", &l1, &same])), 1);
+    }
+}
+
+#[test]
+fn test_dirs() {
+    let (_, a, b) = trees("test_cli_dirs");
+
+    // the difference is in a subdirectory, CVS is ignored by default
+    assert_eq!(status(&filecmp(&[&"-d", &a, &b])), 0);
+    let output = filecmp(&[&"-d", &"-r", &a, &b]);
+    assert_eq!(status(&output), 1);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("diff.txt"));
+
+    let output = filecmp(&[&"-r", &"-x", &"sub", &a, &b]);
+    assert_eq!(status(&output), 1);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("CVS"));
+    assert_eq!(
+        status(&filecmp(&[&"-r", &"-x", &"sub", &"-x", &"CVS", &a, &b])),
+        0
+    );
+    assert_eq!(
+        status(&filecmp(&[&"-r", &"--hide", &"sub", &"-q", &a, &b])),
+        0
+    );
+}
+
+#[test]
+fn test_manifest() {
+    let (test_dir, a, _) = trees("test_cli_manifest");
+    let manifest = test_dir.join("a.manifest");
+
+    let output = filecmp(&[&"manifest", &"-o", &manifest, &a]);
+    assert_eq!(status(&output), 0);
+    assert_eq!(status(&filecmp(&[&"-r", &"-m", &a, &manifest])), 0);
+    fs::write(a.join("sub").join("diff.txt"), "changed").unwrap();
+    assert_eq!(status(&filecmp(&[&"-r", &"-m", &a, &manifest])), 1);
+    assert_eq!(
+        status(&filecmp(&[&"manifest", &test_dir.join("missing")])),
+        2
+    );
+}
+
+#[test]
+fn test_mtree() {
+    let (test_dir, a, _) = trees("test_cli_mtree");
+    let spec = test_dir.join("a.mtree");
+
+    let output = filecmp(&[&"manifest", &"--mtree", &"-x", &"sub", &"-o", &spec, &a]);
+    assert_eq!(status(&output), 0);
+    assert!(!fs::read_to_string(&spec).unwrap().contains("diff.txt"));
+    assert_eq!(status(&filecmp(&[&"-r", &"--mtree", &a, &spec])), 1);
+    assert_eq!(
+        status(&filecmp(&[&"-r", &"--mtree", &"-x", &"sub", &a, &spec])),
+        0
+    );
+}
+
+#[test]
+fn test_check() {
+    let (test_dir, a, _) = trees("test_cli_check");
+    fs::write(a.join("abc"), "abc").unwrap();
+    let sha = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    let sums = test_dir.join("SHA256SUMS");
+    let check = |text: String| -> Output {
+        fs::write(&sums, text).unwrap();
+        filecmp(&[&"check", &"-C", &a, &sums])
+    };
+
+    let output = check(format!("{}  abc\n", sha));
+    assert_eq!(status(&output), 0);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "abc: OK\n");
+    assert_eq!(status(&check(format!("{}  same.txt\n", sha))), 1);
+    assert_eq!(status(&check("not a checksum line\n".to_string())), 2);
+    assert_eq!(
+        status(&filecmp(&[&"check", &"-q", &Path::new("missing")])),
+        2
+    );
+}